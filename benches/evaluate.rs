@@ -0,0 +1,59 @@
+use std::hint::black_box;
+
+use auto_redshift::{
+	config::AppConfig,
+	display::calculate_display_settings,
+	schedule::{evaluate_range, evaluate_time, RedshiftLevels, Schedule, SectionBounds, Waketime},
+};
+use chrono::{Duration, NaiveTime};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+fn schedule() -> Schedule {
+	Schedule::new(Waketime::new(7, 0, 0), 4.0, SectionBounds::default(), 30.0, 4.0, RedshiftLevels::default())
+}
+
+/// Every minute of the day
+fn day() -> Vec<NaiveTime> {
+	(0..24 * 60).map(|m| NaiveTime::MIN + Duration::minutes(m)).collect()
+}
+
+fn evaluate(c: &mut Criterion) {
+	let schedule = schedule();
+	let day = day();
+	let mut group = c.benchmark_group("evaluate");
+	group.bench_function("evaluate_time", |b| {
+		b.iter(|| {
+			for t in &day {
+				black_box(evaluate_time(black_box(*t), &schedule));
+			}
+		})
+	});
+	group.bench_function("evaluate_range", |b| {
+		b.iter(|| evaluate_range(NaiveTime::MIN, NaiveTime::MIN, Duration::minutes(1), black_box(&schedule)))
+	});
+	group.finish();
+}
+
+fn display(c: &mut Criterion) {
+	let config = AppConfig {
+		temperature_range: (2500, 6500),
+		brightness_range: (0.5, 1.0),
+		..Default::default()
+	};
+	let redshifts: Vec<f32> = evaluate_range(NaiveTime::MIN, NaiveTime::MIN, Duration::minutes(1), &schedule())
+		.into_iter()
+		.map(|(_, e)| e.redshift)
+		.collect();
+	let mut group = c.benchmark_group("display");
+	group.bench_function("calculate_display_settings", |b| {
+		b.iter(|| {
+			for r in &redshifts {
+				black_box(calculate_display_settings(black_box(*r), &config));
+			}
+		})
+	});
+	group.finish();
+}
+
+criterion_group!(benches, evaluate, display);
+criterion_main!(benches);
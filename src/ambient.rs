@@ -0,0 +1,40 @@
+//! Ambient light sensor input, through the kernel's iio interface
+
+use std::{fs, path::Path};
+
+use crate::config::Ambient;
+
+const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+/// Current illuminance in lux, or `None` if there is no readable sensor. With `device == None`, the first iio device exposing illuminance is used.
+pub fn read_lux(device: Option<&Path>) -> Option<f32> {
+	match device {
+		Some(device) => read_device(device),
+		None => fs::read_dir(IIO_DEVICES).ok()?.flatten().find_map(|entry| read_device(&entry.path())),
+	}
+}
+
+fn read_device(device: &Path) -> Option<f32> {
+	let read = |name: &str| fs::read_to_string(device.join(name)).ok()?.trim().parse::<f32>().ok();
+	if let Some(lux) = read("in_illuminance_input") {
+		return Some(lux);
+	}
+	let raw = read("in_illuminance_raw")?;
+	Some(raw * read("in_illuminance_scale").unwrap_or(1.0))
+}
+
+/// Perceived brightness is roughly logarithmic in lux, so is this. `max_lux` and above map on the top of the range.
+pub fn lux_to_brightness(lux: f32, max_lux: f32, brightness_range: (f32, f32)) -> f32 {
+	let (min, max) = brightness_range;
+	let fraction = ((lux.max(0.0) + 1.0).ln() / (max_lux.max(1.0) + 1.0).ln()).clamp(0.0, 1.0);
+	min + fraction * (max - min)
+}
+
+/// Weighted mix of what the schedule wants and what the room suggests
+pub fn blend(schedule_brightness: f32, lux: Option<f32>, ambient: &Ambient, brightness_range: (f32, f32)) -> f32 {
+	let Some(lux) = lux else {
+		return schedule_brightness;
+	};
+	let weight = ambient.weight.clamp(0.0, 1.0);
+	(1.0 - weight) * schedule_brightness + weight * lux_to_brightness(lux, ambient.max_lux, brightness_range)
+}
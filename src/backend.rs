@@ -0,0 +1,722 @@
+use std::{
+	fmt,
+	process::Command,
+	sync::{Arc, Mutex},
+	time::{Duration, Instant},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::{
+	clock::{Clock, SystemClock, VirtualClock},
+	cmd,
+	config::{AppConfig, BackendKind, Limits},
+	outputs::OutputFilter,
+	require_binary, which,
+};
+
+#[cfg(feature = "wayland")]
+mod wayland;
+#[cfg(feature = "wayland")]
+pub use wayland::Wayland;
+mod gnome;
+pub use gnome::Gnome;
+mod replay;
+pub use replay::Replay;
+mod xrandr;
+pub use xrandr::Xrandr;
+
+/// What a backend can set besides temperature
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+	/// Without it, brightness is neither set nor read, and the daemon goes by temperature alone
+	pub brightness: bool,
+	/// Writes whole gamma ramps, which a lut can be loaded into
+	pub ramps: bool,
+}
+impl Default for Capabilities {
+	fn default() -> Self {
+		Self {
+			brightness: true,
+			ramps: false,
+		}
+	}
+}
+
+/// What `get_*` fail with on backends that can only report back what they set, before anything was
+#[derive(Debug, thiserror::Error)]
+#[error("nothing was set yet")]
+pub struct NothingSet;
+
+pub trait GammaBackend {
+	fn get_temperature(&mut self) -> Result<f32>;
+	fn get_brightness(&mut self) -> Result<f32>;
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()>;
+	/// Called once on startup, to fail early and clearly if the environment can't support the backend
+	fn check(&self) -> Result<()> {
+		Ok(())
+	}
+	/// Called once on startup after `check`, to see that gamma can actually be reached
+	fn probe(&mut self) -> Result<()> {
+		self.get_temperature().map(|_| ())
+	}
+	/// Can change after `probe`, for backends that find out by asking
+	fn capabilities(&self) -> Capabilities {
+		Capabilities::default()
+	}
+	/// Whether outputs were plugged in or out since the last call, for backends that hear of it themselves. `None` leaves it to polling the compositor
+	fn outputs_changed(&mut self) -> Option<bool> {
+		None
+	}
+}
+impl<B: GammaBackend + ?Sized> GammaBackend for Box<B> {
+	fn get_temperature(&mut self) -> Result<f32> {
+		(**self).get_temperature()
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		(**self).get_brightness()
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		(**self).set(temperature, brightness)
+	}
+
+	fn check(&self) -> Result<()> {
+		(**self).check()
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		(**self).probe()
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		(**self).capabilities()
+	}
+
+	fn outputs_changed(&mut self) -> Option<bool> {
+		(**self).outputs_changed()
+	}
+}
+
+/// Talks to [wlr-gamma-service](https://github.com/zoidplex/wlr-gamma-service) over dbus
+#[derive(Clone, Debug, derive_new::new)]
+pub struct Gdbus {
+	/// Seconds gdbus waits for a reply before giving up. Without it a wedged service stalls the whole loop.
+	timeout_s: u64,
+	retries: u32,
+	/// Some builds of the service only do temperature, which `probe` finds out
+	#[new(value = "true")]
+	brightness: bool,
+	/// Answers calls in place of gdbus, if set
+	#[new(default)]
+	replay: Option<Arc<Mutex<Replay>>>,
+}
+impl Gdbus {
+	pub fn with_replay(self, replay: Replay) -> Self {
+		Self {
+			replay: Some(Arc::new(Mutex::new(replay))),
+			..self
+		}
+	}
+
+	pub fn command(&self, method: &str, arg: Option<f32>) -> String {
+		let mut command = format!(
+			"gdbus call -e --timeout {} -d net.zoidplex.wlr_gamma_service -o /net/zoidplex/wlr_gamma_service -m net.zoidplex.wlr_gamma_service.{method}",
+			self.timeout_s
+		);
+		if let Some(arg) = arg {
+			command.push_str(&format!(" {arg}"));
+		}
+		command
+	}
+
+	fn call(&self, method: &str, arg: Option<f32>) -> Result<String> {
+		// replies of a replay come just the same without waiting on them, it's the live service that gets real time to come around
+		let clock: &dyn Clock = match self.replay {
+			Some(_) => &VirtualClock::new(chrono::Utc::now()),
+			None => &SystemClock,
+		};
+		retry(self.retries, clock, || self.call_once(method, arg))
+	}
+
+	fn call_once(&self, method: &str, arg: Option<f32>) -> Result<String> {
+		if let Some(replay) = &self.replay {
+			return replay.lock().expect("nothing panics while holding it").next(method);
+		}
+		let output = cmd(self.command(method, arg));
+		if !output.status.success() {
+			bail!("`{method}` failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+		}
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+
+	fn get(&self, method: &str) -> Result<f32> {
+		let reply = self.call(method, None)?;
+		parse_reply(&reply).with_context(|| format!("unexpected reply to `{method}`: {reply:?}"))
+	}
+}
+
+/// Takes a one-number tuple like `(6500.0,)`, or `(uint32 6500,)`, as gdbus prints values that aren't doubles
+pub fn parse_reply(reply: &str) -> Result<f32> {
+	let extra_characters: &[_] = &['(', ')', ','];
+	let inner = reply.trim().trim_matches(extra_characters);
+	let value = inner.rsplit(char::is_whitespace).next().unwrap_or(inner);
+	Ok(value.parse()?)
+}
+impl GammaBackend for Gdbus {
+	fn get_temperature(&mut self) -> Result<f32> {
+		self.get("temperature.get")
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		self.get("brightness.get")
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		// the service takes whole Kelvin, and interpolation leaves values like 4999.9997
+		self.call("temperature.set", Some(temperature.round()))?;
+		if self.brightness {
+			self.call("brightness.set", Some(brightness))?;
+		}
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		if self.replay.is_some() {
+			return Ok(());
+		}
+		require_binary("gdbus", "install glib2").map(|_| ())
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.get_temperature()?;
+		match self.call_once("brightness.get", None) {
+			// a missing method won't show up on a retry, anything else could be passing
+			Err(e) if e.to_string().contains("UnknownMethod") => {
+				tracing::info!("gamma service has no brightness methods, only setting temperature");
+				self.brightness = false;
+			}
+			Err(_) => {
+				self.get_brightness()?;
+			}
+			Ok(_) => {}
+		}
+		Ok(())
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		Capabilities {
+			brightness: self.brightness,
+			..Default::default()
+		}
+	}
+}
+
+const PLACEHOLDERS: [&str; 2] = ["temperature", "brightness"];
+
+/// Fails on placeholders other than `{temperature}` and `{brightness}`, and on any of `required` missing from `template`
+pub fn check_placeholders(template: &str, required: &[&str]) -> Result<()> {
+	ensure!(!template.trim().is_empty(), "command is empty");
+	let mut found = Vec::new();
+	let mut rest = template;
+	while let Some(start) = rest.find('{') {
+		let len = rest[start..].find('}').with_context(|| format!("unclosed `{{` in {template:?}"))?;
+		let name = &rest[start + 1..start + len];
+		ensure!(
+			PLACEHOLDERS.contains(&name),
+			"unknown placeholder `{{{name}}}` in {template:?}, only {{temperature}} and {{brightness}} are filled in"
+		);
+		found.push(name);
+		rest = &rest[start + len + 1..];
+	}
+	for placeholder in required {
+		ensure!(found.contains(placeholder), "{template:?} is missing the `{{{placeholder}}}` placeholder");
+	}
+	Ok(())
+}
+
+/// Runs a command on every set, with `{temperature}` in Kelvin and `{brightness}` from 0 to 1 filled in.
+/// The template is split into words before substitution and run directly, never through a shell.
+#[derive(Clone, Debug, derive_new::new)]
+pub struct Template {
+	set_command: String,
+	/// Each should print just the value. Without them, the last values set are reported back, and `NothingSet` before the first set.
+	get_temperature_command: Option<String>,
+	get_brightness_command: Option<String>,
+	#[new(default)]
+	last: Option<(f32, f32)>,
+}
+impl Template {
+	pub fn expand(&self, temperature: f32, brightness: f32) -> Vec<String> {
+		let temperature = temperature.round().to_string();
+		let brightness = format!("{brightness:.2}");
+		self.set_command
+			.split_whitespace()
+			.map(|word| word.replace("{temperature}", &temperature).replace("{brightness}", &brightness))
+			.collect()
+	}
+
+	fn get(command: &Option<String>, last: Option<f32>) -> Result<f32> {
+		match command {
+			Some(command) => {
+				let argv: Vec<String> = command.split_whitespace().map(str::to_owned).collect();
+				let stdout = run(&argv)?;
+				stdout.trim().parse().with_context(|| format!("unexpected output of `{command}`: {stdout:?}"))
+			}
+			None => last.ok_or_else(|| NothingSet.into()),
+		}
+	}
+}
+impl GammaBackend for Template {
+	fn get_temperature(&mut self) -> Result<f32> {
+		Self::get(&self.get_temperature_command, self.last.map(|(t, _)| t))
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		Self::get(&self.get_brightness_command, self.last.map(|(_, b)| b))
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		run(&self.expand(temperature, brightness))?;
+		self.last = Some((temperature, brightness));
+		Ok(())
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		Capabilities {
+			brightness: self.set_command.contains("{brightness}"),
+			..Default::default()
+		}
+	}
+
+	fn check(&self) -> Result<()> {
+		match self.set_command.split_whitespace().next() {
+			Some(program) if program.contains('/') => Ok(()),
+			Some(program) => require_binary(program, "check `set_command`").map(|_| ()),
+			None => bail!("`set_command` is empty"),
+		}
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		match self.get_temperature_command {
+			Some(_) => self.get_temperature().map(|_| ()),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Runs `argv` without a shell, returning its stdout
+fn run(argv: &[String]) -> Result<String> {
+	let (program, args) = argv.split_first().context("empty command")?;
+	let output = Command::new(program)
+		.args(args)
+		.output()
+		.with_context(|| format!("failed to run `{program}`"))?;
+	if !output.status.success() {
+		bail!("`{}` failed: {}", argv.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+	}
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Sets the extra `targets` to whatever goes to `primary`, which reads and probes go to alone.
+/// A failing target is only warned about, so that a flaky lamp can't hold the screen back.
+#[derive(derive_new::new)]
+pub struct WithTargets<B> {
+	primary: B,
+	targets: Vec<Box<dyn GammaBackend>>,
+}
+impl<B: GammaBackend> GammaBackend for WithTargets<B> {
+	fn get_temperature(&mut self) -> Result<f32> {
+		self.primary.get_temperature()
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		self.primary.get_brightness()
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		self.primary.set(temperature, brightness)?;
+		for (i, target) in self.targets.iter_mut().enumerate() {
+			if let Err(e) = target.set(temperature, brightness) {
+				tracing::warn!("failed to set target {i}: {e:#}");
+			}
+		}
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		self.primary.check()?;
+		self.targets
+			.iter()
+			.enumerate()
+			.try_for_each(|(i, t)| t.check().with_context(|| format!("target {i}")))
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.primary.probe()
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		self.primary.capabilities()
+	}
+
+	fn outputs_changed(&mut self) -> Option<bool> {
+		self.primary.outputs_changed()
+	}
+}
+
+/// Clamps everything on its way to `inner` into `limits`, as the last line of defense against bad math upstream
+#[derive(Clone, Debug, derive_new::new)]
+pub struct Clamped<B> {
+	inner: B,
+	limits: Limits,
+	/// Last set, as it was asked for and as it went to `inner`
+	#[new(default)]
+	last: Option<((f32, f32), (f32, f32))>,
+}
+impl<B> Clamped<B> {
+	/// What was asked for, if `read` is still what that was clamped to, so that a value past the limits doesn't read back as changed
+	fn unclamp(&self, read: f32, pick: impl Fn((f32, f32)) -> f32, epsilon: f32) -> f32 {
+		match self.last {
+			Some((asked, written)) if (read - pick(written)).abs() <= epsilon => pick(asked),
+			_ => read,
+		}
+	}
+}
+impl<B: GammaBackend> GammaBackend for Clamped<B> {
+	fn get_temperature(&mut self) -> Result<f32> {
+		// backends may round to whole Kelvin
+		let read = self.inner.get_temperature()?;
+		Ok(self.unclamp(read, |(t, _)| t, 1.0))
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		let read = self.inner.get_brightness()?;
+		Ok(self.unclamp(read, |(_, b)| b, 0.01))
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		ensure!(
+			temperature.is_finite() && brightness.is_finite(),
+			"refusing to set {temperature}K at brightness {brightness}"
+		);
+		let clamp = |name: &str, value: f32, (min, max): (f32, f32)| {
+			let clamped = value.clamp(min, max);
+			if clamped != value {
+				tracing::warn!("{name} {value} is outside of limits [{min}, {max}], clamping to {clamped}");
+			}
+			clamped
+		};
+		let clamped = (
+			clamp("temperature", temperature, self.limits.temperature),
+			clamp("brightness", brightness, self.limits.brightness),
+		);
+		self.inner.set(clamped.0, clamped.1)?;
+		self.last = Some(((temperature, brightness), clamped));
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		self.inner.check()
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.inner.probe()
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		self.inner.capabilities()
+	}
+
+	fn outputs_changed(&mut self) -> Option<bool> {
+		self.inner.outputs_changed()
+	}
+}
+
+/// Shifts temperature by `temperature_bias` and scales brightness by `brightness_scale` on the way to `inner`, and back on the way out,
+/// so that everything upstream keeps seeing the values it asked for
+#[derive(Clone, Debug, derive_new::new)]
+pub struct Adjusted<B> {
+	inner: B,
+	temperature_bias: f32,
+	brightness_scale: f32,
+}
+impl<B: GammaBackend> GammaBackend for Adjusted<B> {
+	fn get_temperature(&mut self) -> Result<f32> {
+		Ok(self.inner.get_temperature()? - self.temperature_bias)
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		Ok(self.inner.get_brightness()? / self.brightness_scale)
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		self.inner.set(temperature + self.temperature_bias, brightness * self.brightness_scale)
+	}
+
+	fn check(&self) -> Result<()> {
+		self.inner.check()
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.inner.probe()
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		self.inner.capabilities()
+	}
+
+	fn outputs_changed(&mut self) -> Option<bool> {
+		self.inner.outputs_changed()
+	}
+}
+
+/// Per-channel multipliers for `temperature`, normalized so that 6500K is neutral
+pub fn whitepoint(temperature: f32) -> [f32; 3] {
+	// Tanner Helland's fit of the blackbody curve, in 0..255
+	fn rgb(kelvin: f32) -> [f32; 3] {
+		let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+		let r = match t <= 66.0 {
+			true => 255.0,
+			false => 329.698_73 * (t - 60.0).powf(-0.133_204_76),
+		};
+		let g = match t <= 66.0 {
+			true => 99.470_8 * t.ln() - 161.119_57,
+			false => 288.122_16 * (t - 60.0).powf(-0.075_514_85),
+		};
+		let b = match t {
+			t if t >= 66.0 => 255.0,
+			t if t <= 19.0 => 0.0,
+			t => 138.517_73 * (t - 10.0).ln() - 305.044_8,
+		};
+		[r, g, b].map(|c| c.clamp(0.0, 255.0))
+	}
+	let neutral = rgb(6500.0);
+	let target = rgb(temperature);
+	[0, 1, 2].map(|i| (target[i] / neutral[i]).min(1.0))
+}
+
+/// Routes brightness to the actual backlight instead of the gamma ramp, leaving gamma brightness at full
+#[derive(Clone, Debug, derive_new::new)]
+pub struct Backlight<B> {
+	inner: B,
+	device: Option<String>,
+	/// Duration of the fade to a new brightness level. Needs `brillo`, otherwise brightness is just set.
+	fade_ms: u64,
+}
+impl<B: GammaBackend> Backlight<B> {
+	pub fn set_command(&self, brightness: f32, fade_supported: bool) -> String {
+		let percent = (brightness * 100.0).round();
+		match (self.fade_ms > 0 && fade_supported, &self.device) {
+			(true, Some(device)) => format!("brillo -s {device} -S {percent} -u {}", self.fade_ms * 1000),
+			(true, None) => format!("brillo -S {percent} -u {}", self.fade_ms * 1000),
+			(false, Some(device)) => format!("brightnessctl -d {device} set {percent}%"),
+			(false, None) => format!("brightnessctl set {percent}%"),
+		}
+	}
+
+	fn get_command(&self) -> String {
+		match &self.device {
+			Some(device) => format!("brightnessctl -m -d {device} info"),
+			None => "brightnessctl -m info".to_owned(),
+		}
+	}
+}
+impl<B: GammaBackend> GammaBackend for Backlight<B> {
+	fn get_temperature(&mut self) -> Result<f32> {
+		self.inner.get_temperature()
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		// `device,class,current,percent%,max`
+		let output = cmd(self.get_command());
+		let stdout = String::from_utf8_lossy(&output.stdout);
+		let percent = stdout.trim().split(',').nth(3).map(|p| p.trim_end_matches('%'));
+		let percent: f32 = percent
+			.and_then(|p| p.parse().ok())
+			.with_context(|| format!("unexpected brightnessctl output: {stdout:?}"))?;
+		Ok(percent / 100.0)
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		self.inner.set(temperature, 1.0)?;
+		let command = self.set_command(brightness, which("brillo").is_some());
+		let output = cmd(&command);
+		if !output.status.success() {
+			bail!("`{command}` failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+		}
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		self.inner.check()?;
+		require_binary("brightnessctl", "install brightnessctl, or disable [backlight]")?;
+		if self.fade_ms > 0 && which("brillo").is_none() {
+			tracing::warn!("brillo not found on PATH, backlight will be set without fading");
+		}
+		Ok(())
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.inner.probe()
+	}
+
+	/// Brightness goes to the backlight, whatever the gamma backend can do
+	fn capabilities(&self) -> Capabilities {
+		Capabilities {
+			brightness: true,
+			..self.inner.capabilities()
+		}
+	}
+
+	fn outputs_changed(&mut self) -> Option<bool> {
+		self.inner.outputs_changed()
+	}
+}
+
+pub fn from_config(config: &AppConfig) -> Result<Box<dyn GammaBackend>> {
+	let outputs = OutputFilter::new(&config.outputs_include, &config.outputs_exclude)?;
+	let gamma: Box<dyn GammaBackend> = match config.backend {
+		BackendKind::Gdbus => {
+			if !outputs.is_empty() {
+				tracing::warn!("wlr-gamma-service sets all outputs at once, ignoring outputs_include/outputs_exclude");
+			}
+			let gdbus = Gdbus::new(config.gdbus.timeout_s, config.gdbus.retries);
+			match &config.gdbus.replay {
+				Some(path) => Box::new(gdbus.with_replay(Replay::read(path)?)),
+				None => Box::new(gdbus),
+			}
+		}
+		#[cfg(feature = "wayland")]
+		BackendKind::Wayland => {
+			let wayland = Wayland::new(outputs);
+			match &config.lut {
+				#[cfg(feature = "lut")]
+				Some(path) => Box::new(wayland.with_lut(crate::lut::Lut::read(path)?)),
+				#[cfg(not(feature = "lut"))]
+				Some(_) => bail!("`lut` is set, but this was built without the `lut` feature"),
+				None => Box::new(wayland),
+			}
+		}
+		#[cfg(not(feature = "wayland"))]
+		BackendKind::Wayland => bail!("built without the `wayland` feature"),
+		BackendKind::Xrandr => Box::new(Xrandr::new(outputs)),
+		BackendKind::Gnome => {
+			if !outputs.is_empty() {
+				tracing::warn!("GNOME night light covers all outputs at once, ignoring outputs_include/outputs_exclude");
+			}
+			Box::new(Gnome::new())
+		}
+		BackendKind::Command => {
+			let command = &config.command;
+			Box::new(Template::new(
+				command.set_command.clone(),
+				command.get_temperature_command.clone(),
+				command.get_brightness_command.clone(),
+			))
+		}
+	};
+	if config.lut.is_some() && !gamma.capabilities().ramps {
+		bail!("only backends that write whole gamma ramps, like wayland, can have a lut loaded in");
+	}
+	let screen: Box<dyn GammaBackend> = match config.backlight.enabled {
+		true => Box::new(Backlight::new(gamma, config.backlight.device.clone(), config.backlight.brightness_fade_ms)),
+		false => gamma,
+	};
+	let all: Box<dyn GammaBackend> = match config.targets.is_empty() {
+		true => screen,
+		false => {
+			let targets = config
+				.targets
+				.iter()
+				.map(|t| Box::new(Template::new(t.set_command.clone(), None, None)) as Box<dyn GammaBackend>)
+				.collect();
+			Box::new(WithTargets::new(screen, targets))
+		}
+	};
+	// adjusted before clamping, so that the limits hold over the bias and scale too
+	Ok(Box::new(Adjusted::new(
+		Clamped::new(all, config.limits),
+		config.temperature_bias,
+		config.brightness_scale,
+	)))
+}
+
+/// How long a run of sets took each
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Latency {
+	pub count: u32,
+	pub min: Duration,
+	pub avg: Duration,
+	pub max: Duration,
+}
+impl fmt::Display for Latency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+		write!(
+			f,
+			"{} sets: min {:.1}ms, avg {:.1}ms, max {:.1}ms",
+			self.count,
+			ms(self.min),
+			ms(self.avg),
+			ms(self.max)
+		)
+	}
+}
+
+/// Sets the same values `count` times in a row, timing each
+pub fn measure(backend: &mut dyn GammaBackend, temperature: f32, brightness: f32, count: u32) -> Result<Latency> {
+	ensure!(count > 0, "nothing to measure with a count of 0");
+	let mut times = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		let start = Instant::now();
+		backend.set(temperature, brightness)?;
+		times.push(start.elapsed());
+	}
+	Ok(Latency {
+		count,
+		min: *times.iter().min().unwrap(),
+		avg: times.iter().sum::<Duration>() / count,
+		max: *times.iter().max().unwrap(),
+	})
+}
+
+/// Keeps calling `f` until it succeeds, backing off from 250ms up to 5s, and gives up with its last error once `timeout` is up.
+/// For services that come up along with the session, and may not be there yet when we start.
+pub fn wait_ready<T>(timeout: Duration, clock: &dyn Clock, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+	const MAX_BACKOFF: Duration = Duration::from_secs(5);
+	let deadline = clock.now() + chrono::Duration::from_std(timeout).expect("fits in chrono");
+	let mut backoff = Duration::from_millis(250);
+	let mut attempt = 1;
+	loop {
+		match f() {
+			Ok(v) => return Ok(v),
+			Err(e) => {
+				let Some(left) = (deadline - clock.now()).to_std().ok().filter(|d| !d.is_zero()) else {
+					return Err(e.context(format!("still not ready after {attempt} attempts over {}s", timeout.as_secs())));
+				};
+				tracing::debug!("not ready yet (attempt {attempt}), trying again in {backoff:?}: {e:#}");
+				clock.sleep(backoff.min(left));
+				backoff = (backoff * 2).min(MAX_BACKOFF);
+				attempt += 1;
+			}
+		}
+	}
+}
+
+/// Runs `f` up to `retries + 1` times, backing off a second longer after each failure
+pub fn retry<T>(retries: u32, clock: &dyn Clock, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+	let mut attempt = 0;
+	loop {
+		match f() {
+			Ok(v) => return Ok(v),
+			Err(e) if attempt >= retries => return Err(e),
+			Err(e) => {
+				attempt += 1;
+				tracing::warn!("attempt {attempt}/{} failed: {e:#}", retries + 1);
+				clock.sleep(Duration::from_secs(attempt as u64));
+			}
+		}
+	}
+}
@@ -0,0 +1,80 @@
+use anyhow::{Context, Result};
+
+use super::{parse_reply, run, Capabilities, GammaBackend};
+use crate::require_binary;
+
+const SCHEMA: &str = "org.gnome.settings-daemon.plugins.color";
+
+/// Drives GNOME's own night light through `gsettings`, for GNOME on Wayland, which has no gamma control protocol to go through
+#[derive(Clone, Debug, Default)]
+pub struct Gnome {
+	/// Night light is switched on, with its own schedule out of the way
+	taken_over: bool,
+}
+impl Gnome {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	fn gsettings(args: &[&str]) -> Vec<String> {
+		["gsettings"].iter().chain(args).map(|s| s.to_string()).collect()
+	}
+
+	/// Keeps night light on all day, so that only the temperature we set decides what it does
+	pub fn take_over_commands() -> Vec<Vec<String>> {
+		[
+			["set", SCHEMA, "night-light-enabled", "true"],
+			["set", SCHEMA, "night-light-schedule-automatic", "false"],
+			["set", SCHEMA, "night-light-schedule-from", "0.0"],
+			["set", SCHEMA, "night-light-schedule-to", "23.99"],
+		]
+		.iter()
+		.map(|args| Self::gsettings(args))
+		.collect()
+	}
+
+	/// GNOME takes whole Kelvin, from 1000 to 10000
+	pub fn set_command(temperature: f32) -> Vec<String> {
+		let temperature = format!("uint32 {}", temperature.clamp(1000.0, 10000.0).round());
+		Self::gsettings(&["set", SCHEMA, "night-light-temperature", &temperature])
+	}
+}
+impl GammaBackend for Gnome {
+	fn get_temperature(&mut self) -> Result<f32> {
+		let reply = run(&Self::gsettings(&["get", SCHEMA, "night-light-temperature"]))?;
+		parse_reply(&reply).with_context(|| format!("unexpected night-light-temperature: {reply:?}"))
+	}
+
+	/// Night light has no brightness, so this is always full
+	fn get_brightness(&mut self) -> Result<f32> {
+		Ok(1.0)
+	}
+
+	fn set(&mut self, temperature: f32, _brightness: f32) -> Result<()> {
+		if !self.taken_over {
+			for command in Self::take_over_commands() {
+				run(&command)?;
+			}
+			self.taken_over = true;
+		}
+		run(&Self::set_command(temperature))?;
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		require_binary("gsettings", "install glib2")?;
+		Ok(())
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.get_temperature().context("no GNOME night light settings to drive")?;
+		Ok(())
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		Capabilities {
+			brightness: false,
+			..Default::default()
+		}
+	}
+}
@@ -0,0 +1,48 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	path::Path,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Recorded gdbus replies, answered in place of the service, so that the real parsing runs against real-world output
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+	/// Stdout of each call to a method in order, or its stderr if it failed
+	replies: HashMap<String, VecDeque<Result<String, String>>>,
+}
+impl Replay {
+	pub fn read(path: &Path) -> Result<Self> {
+		let s = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+		Self::parse(&s).with_context(|| format!("bad replay in {}", path.display()))
+	}
+
+	/// Lines of `method: stdout`, or `method! stderr` for a call that failed, like `temperature.get: (6500.0,)`. `#` starts a comment line.
+	pub fn parse(s: &str) -> Result<Self> {
+		let mut replies: HashMap<String, VecDeque<Result<String, String>>> = HashMap::new();
+		for line in s.lines().filter(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#')) {
+			let Some(split) = line.find([':', '!']) else {
+				bail!("expected `method: reply` or `method! error`, got {line:?}");
+			};
+			let (method, reply) = (line[..split].trim(), line[split + 1..].trim().to_owned());
+			let reply = match &line[split..split + 1] {
+				":" => Ok(reply),
+				_ => Err(reply),
+			};
+			replies.entry(method.to_owned()).or_default().push_back(reply);
+		}
+		Ok(Self { replies })
+	}
+
+	/// Next recorded reply to `method`, the last one repeating once they run out. Methods without any, like the setters usually are, reply with `()`.
+	pub fn next(&mut self, method: &str) -> Result<String> {
+		let Some(replies) = self.replies.get_mut(method) else {
+			return Ok("()".to_owned());
+		};
+		let reply = match replies.len() {
+			1 => replies[0].clone(),
+			_ => replies.pop_front().expect("never emptied"),
+		};
+		reply.map_err(|stderr| anyhow!("`{method}` failed: {stderr}"))
+	}
+}
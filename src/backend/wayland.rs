@@ -0,0 +1,267 @@
+use std::{
+	fs::File,
+	io::{Seek, Write},
+	os::fd::AsFd,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use wayland_client::{
+	delegate_noop,
+	protocol::{
+		wl_output::{self, WlOutput},
+		wl_registry,
+	},
+	Connection, Dispatch, EventQueue, QueueHandle,
+};
+use wayland_protocols_wlr::gamma_control::v1::client::{
+	zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1,
+	zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+};
+
+use super::{whitepoint, Capabilities, GammaBackend, NothingSet};
+#[cfg(feature = "lut")]
+use crate::lut::Lut;
+use crate::outputs::OutputFilter;
+
+/// Stand-in, so that ramps are built the same way without the feature
+#[cfg(not(feature = "lut"))]
+struct Lut;
+#[cfg(not(feature = "lut"))]
+impl Lut {
+	fn sample(&self, _channel: usize, x: f32) -> f32 {
+		x
+	}
+}
+
+/// Sets gamma ramps straight through wlr-gamma-control, no service in between.
+/// The compositor drops our ramps as soon as the connection closes, so it's held for as long as the backend lives, and re-established if the compositor goes away.
+pub struct Wayland {
+	outputs: OutputFilter,
+	session: Option<Session>,
+	/// Protocol has no way to read gamma back, so this is what we report
+	last: Option<(f32, f32)>,
+	/// Base ramp for temperature and brightness to be applied on top of; linear if not given
+	lut: Option<Lut>,
+}
+impl Wayland {
+	/// Only takes control of the outputs that pass `outputs`, leaving gamma of the rest to whoever else wants it
+	pub fn new(outputs: OutputFilter) -> Self {
+		Self {
+			outputs,
+			session: None,
+			last: None,
+			lut: None,
+		}
+	}
+
+	#[cfg(feature = "lut")]
+	pub fn with_lut(mut self, lut: Lut) -> Self {
+		self.lut = Some(lut);
+		self
+	}
+
+	fn session(&mut self) -> Result<&mut Session> {
+		if self.session.is_none() {
+			self.session = Some(Session::connect(&self.outputs)?);
+		}
+		Ok(self.session.as_mut().unwrap())
+	}
+}
+impl GammaBackend for Wayland {
+	fn get_temperature(&mut self) -> Result<f32> {
+		self.last.map(|(t, _)| t).ok_or_else(|| NothingSet.into())
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		self.last.map(|(_, b)| b).ok_or_else(|| NothingSet.into())
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		let lut = self.lut.as_ref();
+		if let Some(session) = &mut self.session {
+			match session.set(temperature, brightness, lut) {
+				Ok(()) => {
+					self.last = Some((temperature, brightness));
+					return Ok(());
+				}
+				Err(e) => {
+					tracing::warn!("wayland connection lost, reconnecting: {e:#}");
+					self.session = None;
+				}
+			}
+		}
+		let session = self.session.insert(Session::connect(&self.outputs)?);
+		session.set(temperature, brightness, lut)?;
+		self.last = Some((temperature, brightness));
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		match &self.session {
+			Some(_) => Ok(()),
+			None => Session::connect(&self.outputs).map(|_| ()),
+		}
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		self.session().map(|_| ())
+	}
+
+	fn capabilities(&self) -> Capabilities {
+		Capabilities { brightness: true, ramps: true }
+	}
+
+	/// New outputs only get gamma controls on connecting, so on any change the session is dropped, for the next `set` to start over with what's there now
+	fn outputs_changed(&mut self) -> Option<bool> {
+		let Some(session) = &mut self.session else {
+			return Some(false);
+		};
+		let changed = session.hotplugged().unwrap_or_else(|e| {
+			tracing::warn!("wayland connection lost, reconnecting: {e:#}");
+			true
+		});
+		if changed {
+			self.session = None;
+		}
+		Some(changed)
+	}
+}
+
+struct Session {
+	_connection: Connection,
+	queue: EventQueue<State>,
+	state: State,
+}
+impl Session {
+	fn connect(filter: &OutputFilter) -> Result<Self> {
+		let connection = Connection::connect_to_env().context("failed to connect to the wayland compositor")?;
+		let mut queue = connection.new_event_queue();
+		let qh = queue.handle();
+		connection.display().get_registry(&qh, ());
+		let mut state = State::default();
+		queue.roundtrip(&mut state)?;
+
+		// names come in only once outputs are bound
+		queue.roundtrip(&mut state)?;
+
+		let manager = state.manager.clone().ok_or_else(|| anyhow!("compositor doesn't support wlr-gamma-control"))?;
+		for (i, output) in state.outputs.iter_mut().enumerate() {
+			// outputs from before wl_output v4 have no name to filter by, so they only go if there is nothing to filter
+			let included = match &output.name {
+				Some(name) => filter.matches(name),
+				None => filter.is_empty(),
+			};
+			if included {
+				output.control = Some(manager.get_gamma_control(&output.wl, &qh, i));
+			}
+		}
+		queue.roundtrip(&mut state)?;
+		if state.outputs.iter().all(|o| o.control.is_none()) {
+			bail!("no outputs to set gamma on");
+		}
+		state.connected = true;
+
+		Ok(Self {
+			_connection: connection,
+			queue,
+			state,
+		})
+	}
+
+	/// Whether outputs came or went since the last time this was asked
+	fn hotplugged(&mut self) -> Result<bool> {
+		self.queue.roundtrip(&mut self.state)?;
+		Ok(std::mem::take(&mut self.state.hotplugged))
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32, lut: Option<&Lut>) -> Result<()> {
+		let [r, g, b] = whitepoint(temperature).map(|c| c * brightness.clamp(0.0, 1.0));
+		for output in &self.state.outputs {
+			if output.failed {
+				bail!("compositor revoked gamma control, likely because another client took it");
+			}
+			let (Some(control), Some(size)) = (&output.control, output.gamma_size) else {
+				continue;
+			};
+			let mut table = File::from(rustix::fs::memfd_create("auto_redshift-gamma", rustix::fs::MemfdFlags::CLOEXEC)?);
+			for (channel, factor) in [r, g, b].into_iter().enumerate() {
+				for i in 0..size {
+					let x = i as f32 / (size - 1).max(1) as f32;
+					let v = lut.map_or(x, |lut| lut.sample(channel, x)) * factor;
+					table.write_all(&((v * u16::MAX as f32) as u16).to_ne_bytes())?;
+				}
+			}
+			table.rewind()?;
+			control.set_gamma(table.as_fd());
+		}
+		self.queue.roundtrip(&mut self.state)?;
+		Ok(())
+	}
+}
+
+#[derive(Default)]
+struct State {
+	manager: Option<ZwlrGammaControlManagerV1>,
+	outputs: Vec<Output>,
+	/// Done with the outputs there were to begin with, so any more are hotplugged
+	connected: bool,
+	hotplugged: bool,
+}
+
+struct Output {
+	/// Registry name, which removals go by
+	global: u32,
+	wl: WlOutput,
+	name: Option<String>,
+	control: Option<ZwlrGammaControlV1>,
+	/// Sent by the compositor right after we get the control
+	gamma_size: Option<usize>,
+	failed: bool,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+	fn event(state: &mut Self, registry: &wl_registry::WlRegistry, event: wl_registry::Event, _: &(), _: &Connection, qh: &QueueHandle<Self>) {
+		match event {
+			wl_registry::Event::Global { name, interface, version } => match interface.as_str() {
+				"wl_output" => {
+					state.outputs.push(Output {
+						global: name,
+						wl: registry.bind(name, version.min(4), qh, state.outputs.len()),
+						name: None,
+						control: None,
+						gamma_size: None,
+						failed: false,
+					});
+					state.hotplugged |= state.connected;
+				}
+				"zwlr_gamma_control_manager_v1" => state.manager = Some(registry.bind(name, 1, qh, ())),
+				_ => {}
+			},
+			wl_registry::Event::GlobalRemove { name } => state.hotplugged |= state.outputs.iter().any(|o| o.global == name),
+			_ => {}
+		}
+	}
+}
+
+/// User data is the index into `State::outputs`
+impl Dispatch<ZwlrGammaControlV1, usize> for State {
+	fn event(state: &mut Self, _: &ZwlrGammaControlV1, event: zwlr_gamma_control_v1::Event, i: &usize, _: &Connection, _: &QueueHandle<Self>) {
+		let output = &mut state.outputs[*i];
+		match event {
+			zwlr_gamma_control_v1::Event::GammaSize { size } => output.gamma_size = Some(size as usize),
+			zwlr_gamma_control_v1::Event::Failed => output.failed = true,
+			_ => {}
+		}
+	}
+}
+
+/// User data is the index into `State::outputs`
+impl Dispatch<WlOutput, usize> for State {
+	fn event(state: &mut Self, _: &WlOutput, event: wl_output::Event, i: &usize, _: &Connection, _: &QueueHandle<Self>) {
+		if let wl_output::Event::Name { name } = event {
+			state.outputs[*i].name = Some(name);
+		}
+	}
+}
+
+delegate_noop!(State: ignore ZwlrGammaControlManagerV1);
@@ -0,0 +1,81 @@
+use anyhow::{bail, ensure, Result};
+
+use super::{run, whitepoint, GammaBackend, NothingSet};
+use crate::{outputs::OutputFilter, require_binary};
+
+/// Sets gamma on X11 through the `xrandr` cli, one output at a time
+pub struct Xrandr {
+	outputs: OutputFilter,
+	/// xrandr can't give back a temperature, so this is what we report
+	last: Option<(f32, f32)>,
+}
+impl Xrandr {
+	pub fn new(outputs: OutputFilter) -> Self {
+		Self { outputs, last: None }
+	}
+
+	/// Every output with a monitor plugged in that passes the filter
+	pub fn connected(&self) -> Result<Vec<String>> {
+		let query = run(&["xrandr".to_owned(), "--query".to_owned()])?;
+		Ok(parse_connected(&query).into_iter().filter(|name| self.outputs.matches(name)).collect())
+	}
+
+	/// xrandr raises each channel to `1 / gamma`, so the multipliers are only approximated, with brightness applied linearly on top
+	pub fn command(output: &str, temperature: f32, brightness: f32) -> Vec<String> {
+		let [r, g, b] = whitepoint(temperature).map(|c| c.max(0.1));
+		[
+			"xrandr".to_owned(),
+			"--output".to_owned(),
+			output.to_owned(),
+			"--gamma".to_owned(),
+			format!("{r:.3}:{g:.3}:{b:.3}"),
+			"--brightness".to_owned(),
+			format!("{:.3}", brightness.clamp(0.0, 1.0)),
+		]
+		.into()
+	}
+}
+impl GammaBackend for Xrandr {
+	fn get_temperature(&mut self) -> Result<f32> {
+		self.last.map(|(t, _)| t).ok_or_else(|| NothingSet.into())
+	}
+
+	fn get_brightness(&mut self) -> Result<f32> {
+		self.last.map(|(_, b)| b).ok_or_else(|| NothingSet.into())
+	}
+
+	fn set(&mut self, temperature: f32, brightness: f32) -> Result<()> {
+		let outputs = self.connected()?;
+		if outputs.is_empty() {
+			bail!("no connected outputs to set gamma on");
+		}
+		for output in outputs {
+			run(&Self::command(&output, temperature, brightness))?;
+		}
+		self.last = Some((temperature, brightness));
+		Ok(())
+	}
+
+	fn check(&self) -> Result<()> {
+		require_binary("xrandr", "install xrandr (xorg-xrandr)")?;
+		ensure!(std::env::var_os("DISPLAY").is_some(), "$DISPLAY isn't set, so there is no X server to talk to");
+		Ok(())
+	}
+
+	fn probe(&mut self) -> Result<()> {
+		ensure!(!self.connected()?.is_empty(), "no connected outputs to set gamma on");
+		Ok(())
+	}
+}
+
+/// Names out of `xrandr --query` lines like `HDMI-1 connected primary 1920x1080+0+0 ...`
+fn parse_connected(query: &str) -> Vec<String> {
+	query
+		.lines()
+		.filter_map(|line| {
+			let mut words = line.split_whitespace();
+			let name = words.next()?;
+			(words.next() == Some("connected")).then(|| name.to_owned())
+		})
+		.collect()
+}
@@ -0,0 +1,43 @@
+//! Battery charge, through the kernel's power_supply interface
+
+use std::{fs, path::Path};
+
+use crate::config::Battery;
+
+const POWER_SUPPLIES: &str = "/sys/class/power_supply";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Charge {
+	/// 0 to 100
+	pub percent: f32,
+	/// Running off it, rather than off AC
+	pub discharging: bool,
+}
+
+/// `None` if there is no readable battery. With `device == None`, the first power supply of type `Battery` is used.
+pub fn read_charge(device: Option<&Path>) -> Option<Charge> {
+	match device {
+		Some(device) => read_device(device),
+		None => fs::read_dir(POWER_SUPPLIES)
+			.ok()?
+			.flatten()
+			.filter(|entry| fs::read_to_string(entry.path().join("type")).is_ok_and(|t| t.trim() == "Battery"))
+			.find_map(|entry| read_device(&entry.path())),
+	}
+}
+
+fn read_device(device: &Path) -> Option<Charge> {
+	let read = |name: &str| fs::read_to_string(device.join(name)).ok().map(|s| s.trim().to_owned());
+	Some(Charge {
+		percent: read("capacity")?.parse().ok()?,
+		discharging: read("status")? == "Discharging",
+	})
+}
+
+/// Brightness scaled by `battery.factor` while discharging at or below `battery.below_percent`, but not below the bottom of `brightness_range`
+pub fn scale(brightness: f32, charge: Option<Charge>, battery: &Battery, brightness_range: (f32, f32)) -> f32 {
+	match charge {
+		Some(c) if c.discharging && c.percent <= battery.below_percent => (brightness * battery.factor.clamp(0.0, 1.0)).max(brightness_range.0),
+		_ => brightness,
+	}
+}
@@ -0,0 +1,144 @@
+use std::{
+	io::BufReader,
+	process::Command,
+	time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use ical::{parser::ical::component::IcalEvent, IcalParser};
+
+/// Time taken up by a busy event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+	pub start: DateTime<Utc>,
+	pub end: DateTime<Utc>,
+}
+
+/// Busy events of an ics file. Events marked `TRANSP:TRANSPARENT` (free) and cancelled ones are left out.
+/// Times with a `TZID` are taken as local, and recurring events only count for their first occurrence.
+pub fn parse(ics: &str) -> Result<Vec<Span>> {
+	let mut spans = Vec::new();
+	for calendar in IcalParser::new(BufReader::new(ics.as_bytes())) {
+		for event in calendar.context("malformed ics")?.events {
+			if let Some(span) = busy_span(&event)? {
+				spans.push(span);
+			}
+		}
+	}
+	Ok(spans)
+}
+
+pub fn busy_at(spans: &[Span], now: DateTime<Utc>) -> bool {
+	spans.iter().any(|s| s.start <= now && now < s.end)
+}
+
+fn busy_span(event: &IcalEvent) -> Result<Option<Span>> {
+	let property = |name: &str| event.properties.iter().find(|p| p.name == name).and_then(|p| p.value.as_deref());
+	if property("TRANSP") == Some("TRANSPARENT") || property("STATUS") == Some("CANCELLED") {
+		return Ok(None);
+	}
+	let Some(start) = property("DTSTART") else {
+		return Ok(None);
+	};
+	let (start, all_day) = parse_time(start)?;
+	let end = match (property("DTEND"), property("DURATION")) {
+		(Some(end), _) => parse_time(end)?.0,
+		(None, Some(duration)) => start + parse_duration(duration)?,
+		(None, None) if all_day => start + chrono::Duration::days(1),
+		(None, None) => return Ok(None),
+	};
+	Ok(Some(Span { start, end }))
+}
+
+/// `20261014T100000Z` is UTC, `20261014T100000` is local, `20261014` is the local midnight of an all-day event
+fn parse_time(s: &str) -> Result<(DateTime<Utc>, bool)> {
+	let local = |naive: NaiveDateTime| Local.from_local_datetime(&naive).earliest().map(|t| t.with_timezone(&Utc));
+	if let Some(utc) = s.strip_suffix('Z') {
+		let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").with_context(|| format!("bad time {s:?}"))?;
+		return Ok((Utc.from_utc_datetime(&naive), false));
+	}
+	let (naive, all_day) = match NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S") {
+		Ok(naive) => (naive, false),
+		Err(_) => (
+			NaiveDate::parse_from_str(s, "%Y%m%d")
+				.with_context(|| format!("bad time {s:?}"))?
+				.and_time(Default::default()),
+			true,
+		),
+	};
+	let time = local(naive).with_context(|| format!("{s:?} doesn't exist in the local timezone"))?;
+	Ok((time, all_day))
+}
+
+/// Only the `P1DT2H30M`-like subset, which is what calendars put out
+fn parse_duration(s: &str) -> Result<chrono::Duration> {
+	let Some(rest) = s.strip_prefix('P') else {
+		bail!("bad duration {s:?}");
+	};
+	let mut total = chrono::Duration::zero();
+	let mut number = String::new();
+	for c in rest.chars() {
+		match c {
+			'T' => continue,
+			c if c.is_ascii_digit() => number.push(c),
+			unit => {
+				let n: i64 = number.parse().with_context(|| format!("bad duration {s:?}"))?;
+				number.clear();
+				total += match unit {
+					'W' => chrono::Duration::weeks(n),
+					'D' => chrono::Duration::days(n),
+					'H' => chrono::Duration::hours(n),
+					'M' => chrono::Duration::minutes(n),
+					'S' => chrono::Duration::seconds(n),
+					_ => bail!("bad duration {s:?}"),
+				};
+			}
+		}
+	}
+	Ok(total)
+}
+
+/// Busy events of `source`, fetched again once they're older than `refresh`
+#[derive(Clone, Debug)]
+pub struct Calendar {
+	source: String,
+	refresh: Duration,
+	spans: Vec<Span>,
+	fetched: Option<Instant>,
+}
+impl Calendar {
+	pub fn new(source: String, refresh: Duration) -> Self {
+		Self {
+			source,
+			refresh,
+			spans: Vec::new(),
+			fetched: None,
+		}
+	}
+
+	/// If it can't be fetched, the events from last time are kept
+	pub fn busy(&mut self, now: DateTime<Utc>) -> bool {
+		if self.fetched.is_none_or(|t| t.elapsed() >= self.refresh) {
+			self.fetched = Some(Instant::now());
+			match self.fetch().and_then(|ics| parse(&ics)) {
+				Ok(spans) => self.spans = spans,
+				Err(e) => tracing::warn!("failed to refresh the calendar from {}: {e:#}", self.source),
+			}
+		}
+		busy_at(&self.spans, now)
+	}
+
+	fn fetch(&self) -> Result<String> {
+		match self.source.starts_with("http://") || self.source.starts_with("https://") {
+			true => {
+				let output = Command::new("curl").args(["-fsSL", &self.source]).output().context("failed to run curl")?;
+				if !output.status.success() {
+					bail!("curl failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+				}
+				Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+			}
+			false => std::fs::read_to_string(&self.source).with_context(|| format!("failed to read {}", self.source)),
+		}
+	}
+}
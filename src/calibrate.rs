@@ -0,0 +1,73 @@
+use std::{
+	io::{BufRead, Write},
+	path::Path,
+};
+
+use anyhow::{Context, Result};
+use toml_edit::{value, Array, DocumentMut};
+
+/// From `from` down to `to`, both included, `step` apart
+pub fn steps_down(from: f32, to: f32, step: f32) -> Vec<f32> {
+	let mut steps = Vec::new();
+	let mut v = from;
+	while v > to {
+		steps.push(v);
+		v -= step;
+	}
+	steps.push(to);
+	steps
+}
+
+/// Goes through `candidates`, showing each with `show`, for as long as the user finds them comfortable. Returns the last one that was.
+/// `None` if not even the first one is.
+pub fn pick(
+	candidates: &[f32],
+	unit: &str,
+	mut show: impl FnMut(f32) -> Result<()>,
+	input: &mut impl BufRead,
+	output: &mut impl Write,
+) -> Result<Option<f32>> {
+	let mut comfortable = None;
+	for &candidate in candidates {
+		show(candidate)?;
+		write!(output, "{candidate}{unit}: still comfortable? [Y/n] ")?;
+		output.flush()?;
+		let mut answer = String::new();
+		if input.read_line(&mut answer)? == 0 {
+			break;
+		}
+		if answer.trim().eq_ignore_ascii_case("n") {
+			break;
+		}
+		comfortable = Some(candidate);
+	}
+	Ok(comfortable)
+}
+
+/// Sets the warm/dim ends of `temperature_range` and `brightness_range` in `doc`, keeping the day ends and everything else as they were.
+/// `redshift`-style keys are folded into the ranges, as they can't be given alongside them.
+pub fn update_ranges(doc: &mut DocumentMut, temperature_min: usize, brightness_min: f32) {
+	let day_end = |doc: &mut DocumentMut, range: &str, redshift_style: [&str; 2]| {
+		let from_range = doc.get(range).and_then(|v| v.as_array()).and_then(|a| a.get(1)).cloned();
+		let from_redshift_style = doc.get(redshift_style[0]).and_then(|v| v.as_value()).cloned();
+		for key in redshift_style {
+			doc.remove(key);
+		}
+		from_range.or(from_redshift_style)
+	};
+	let temperature_max = day_end(doc, "temperature_range", ["temp-day", "temp-night"])
+		.and_then(|v| v.as_integer())
+		.unwrap_or(6500);
+	let brightness_max = day_end(doc, "brightness_range", ["brightness-day", "brightness-night"])
+		.and_then(|v| v.as_float().or_else(|| v.as_integer().map(|i| i as f64)))
+		.unwrap_or(1.0);
+	doc["temperature_range"] = value(Array::from_iter([temperature_min as i64, temperature_max]));
+	doc["brightness_range"] = value(Array::from_iter([(brightness_min as f64 * 100.0).round() / 100.0, brightness_max]));
+}
+
+pub fn write_ranges(path: &Path, temperature_min: usize, brightness_min: f32) -> Result<()> {
+	let contents = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+	let mut doc: DocumentMut = contents.parse().with_context(|| format!("failed to parse {}", path.display()))?;
+	update_ranges(&mut doc, temperature_min, brightness_min);
+	std::fs::write(path, doc.to_string()).with_context(|| format!("failed to write {}", path.display()))
+}
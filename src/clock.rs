@@ -0,0 +1,63 @@
+use std::{cell::Cell, time::Duration};
+
+use chrono::{DateTime, Datelike, Utc};
+
+/// Where the daemon loop gets the time from, and how it waits for it to pass, so that it can be run against a virtual clock
+pub trait Clock {
+	fn now(&self) -> DateTime<Utc>;
+	fn sleep(&self, duration: Duration);
+}
+
+/// Whether `now` could be the actual time, rather than what a board without an RTC boots up with, before NTP gets to it
+pub fn plausible(now: DateTime<Utc>) -> bool {
+	now.year() > 2020
+}
+
+/// Sleeps on `clock` until it reads a `plausible` time
+pub fn wait_until_plausible(clock: &dyn Clock) {
+	const POLL: Duration = Duration::from_secs(10);
+	if plausible(clock.now()) {
+		return;
+	}
+	tracing::warn!("the clock reads {}, which can't be right, waiting for it to be set", clock.now());
+	while !plausible(clock.now()) {
+		clock.sleep(POLL);
+	}
+	tracing::info!("the clock is set now, to {}", clock.now());
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+	fn now(&self) -> DateTime<Utc> {
+		Utc::now()
+	}
+
+	fn sleep(&self, duration: Duration) {
+		std::thread::sleep(duration);
+	}
+}
+
+/// Stands still until slept on, then jumps ahead by as much, right away
+#[derive(Debug)]
+pub struct VirtualClock {
+	now: Cell<DateTime<Utc>>,
+}
+impl VirtualClock {
+	pub fn new(start: DateTime<Utc>) -> Self {
+		Self { now: Cell::new(start) }
+	}
+
+	pub fn advance(&self, duration: Duration) {
+		self.now.set(self.now.get() + chrono::Duration::from_std(duration).expect("fits in chrono"));
+	}
+}
+impl Clock for VirtualClock {
+	fn now(&self) -> DateTime<Utc> {
+		self.now.get()
+	}
+
+	fn sleep(&self, duration: Duration) {
+		self.advance(duration);
+	}
+}
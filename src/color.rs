@@ -0,0 +1,71 @@
+use anyhow::{anyhow, ensure, Result};
+
+/// How far off the blackbody curve, in CIE 1960 uv, a color may be and still be taken for a temperature. Pure green or red is nowhere near any
+const MAX_DUV: f32 = 0.02;
+
+/// Range a color may come out at, past which McCamy's approximation is no longer anywhere near, and backends can't go anyway
+pub const KELVIN_RANGE: (f32, f32) = (1000.0, 10000.0);
+
+/// Light sources by their usual correlated color temperature
+const NAMES: [(&str, f32); 7] = [
+	("candle", 1900.0),
+	("sunset", 2300.0),
+	("incandescent", 2700.0),
+	("warm white", 3000.0),
+	("neutral white", 4000.0),
+	("cool white", 5000.0),
+	("daylight", 6500.0),
+];
+
+/// Approximate correlated color temperature of a hex like `#FFB46B`, or of a light source named like `warm white`
+pub fn to_kelvin(color: &str) -> Result<f32> {
+	let color = color.trim();
+	let kelvin = match color.strip_prefix('#') {
+		Some(hex) => hex_to_kelvin(hex)?,
+		None => NAMES
+			.iter()
+			.find(|(name, _)| name.eq_ignore_ascii_case(color))
+			.map(|&(_, k)| k)
+			.ok_or_else(|| {
+				let names: Vec<&str> = NAMES.iter().map(|(name, _)| *name).collect();
+				anyhow!("unknown color {color:?}, expected a hex like `#FFB46B` or one of {}", names.join(", "))
+			})?,
+	};
+	let (min, max) = KELVIN_RANGE;
+	ensure!(
+		(min..=max).contains(&kelvin),
+		"{color} comes out at {kelvin:.0}K, outside of the [{min}, {max}] a color can stand for"
+	);
+	Ok(kelvin)
+}
+
+/// sRGB to CIE xy chromaticity, then McCamy's cubic from there. Checked against Krystek's fit of the blackbody curve
+fn hex_to_kelvin(hex: &str) -> Result<f32> {
+	ensure!(
+		hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()),
+		"expected 6 hex digits after `#`, got {hex:?}"
+	);
+	let linear = |i: usize| {
+		let c = u8::from_str_radix(&hex[i..i + 2], 16).expect("checked to be hex") as f32 / 255.0;
+		match c <= 0.04045 {
+			true => c / 12.92,
+			false => ((c + 0.055) / 1.055).powf(2.4),
+		}
+	};
+	let (r, g, b) = (linear(0), linear(2), linear(4));
+	let x = 0.4124 * r + 0.3576 * g + 0.1805 * b;
+	let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+	let z = 0.0193 * r + 0.1192 * g + 0.9505 * b;
+	let sum = x + y + z;
+	ensure!(sum > 0.0, "black has no color temperature");
+	let (x, y) = (x / sum, y / sum);
+	let n = (x - 0.3320) / (0.1858 - y);
+	let t = 449.0 * n.powi(3) + 3525.0 * n.powi(2) + 6823.3 * n + 5520.33;
+
+	let (u, v) = (4.0 * x / (-2.0 * x + 12.0 * y + 3.0), 6.0 * y / (-2.0 * x + 12.0 * y + 3.0));
+	let u_blackbody = (0.860_117_8 + 1.541_182_5e-4 * t + 1.286_412e-7 * t * t) / (1.0 + 8.424_202e-4 * t + 7.081_452e-7 * t * t);
+	let v_blackbody = (0.317_398_73 + 4.228_062_5e-5 * t + 4.204_817e-8 * t * t) / (1.0 - 2.897_418_2e-5 * t + 1.614_560_5e-7 * t * t);
+	let duv = (u - u_blackbody).hypot(v - v_blackbody);
+	ensure!(duv <= MAX_DUV, "#{hex} is too far from any white to have a color temperature");
+	Ok(t)
+}
@@ -1,24 +1,626 @@
-use anyhow::Result;
-use serde::{Deserialize, Deserializer};
-use std::path::{Path, PathBuf};
-use v_utils::macros::MyConfigPrimitives;
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use chrono::NaiveTime;
+use serde::{Deserialize, Deserializer, Serialize};
 
-#[derive(Clone, Debug, Default, Deserialize)]
+use crate::{
+	backend::check_placeholders,
+	display::DisplaySettings,
+	error::Error,
+	outputs::OutputFilter,
+	schedule::{DaySection, Offset, TimeWindow, WakePeriod, Waketime},
+};
+use std::{
+	collections::HashMap,
+	fmt,
+	path::{Path, PathBuf},
+};
+
+/// `$XDG_CONFIG_HOME/auto_redshift.toml`, falling back to `~/.config/auto_redshift.toml`
+pub fn default_path() -> PathBuf {
+	let base = std::env::var_os("XDG_CONFIG_HOME")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".config"));
+	base.join("auto_redshift.toml")
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct AppConfig {
-	#[serde(deserialize_with = "deserialize_tuple")]
+	#[serde(default = "AppConfig::default_brightness_range", deserialize_with = "deserialize_tuple")]
 	pub brightness_range: (f32, f32),
-	#[serde(deserialize_with = "deserialize_tuple")]
+	/// Falls back on `color_profile`, and only then on the default. Either end can also be a color, like `["#FFB46B", 6500]` or `["candle", "daylight"]`, see `color::to_kelvin`
+	#[serde(default = "AppConfig::default_temperature_range", deserialize_with = "deserialize_temperature_range")]
 	pub temperature_range: (usize, usize),
+	/// Used to fill in `temperature_range` when it's not given explicitly
+	pub color_profile: Option<ColorProfile>,
+	/// Replaces `temperature_range` when given
+	pub relative_temperature: Option<RelativeTemperature>,
+	/// `redshift`'s names for the ends of the ranges, so that its configs carry over. Can't be mixed with the range they stand for.
+	#[serde(rename = "temp-day")]
+	pub temp_day: Option<usize>,
+	#[serde(rename = "temp-night")]
+	pub temp_night: Option<usize>,
+	#[serde(rename = "brightness-day")]
+	pub brightness_day: Option<f32>,
+	#[serde(rename = "brightness-night")]
+	pub brightness_night: Option<f32>,
+	/// Only required with `--wallpapers`
+	#[serde(default)]
 	pub wallpapers: Wallpapers,
+	/// Like `"7:30"`, for when none is passed on the command line
+	pub waketime: Option<Waketime>,
+	/// Shell command printing the waketime, like an alarm app's next alarm, run on startup. Takes precedence over `waketime`, which is fallen back on if it fails
+	pub waketime_command: Option<String>,
+	/// Evening ramp length depending on the time of year. Takes precedence over `--n_hours` for the months it covers.
+	/// Only the length changes, the ramp still goes up in the same 20 whole steps
+	pub n_hours: Option<SeasonalHours>,
+	/// Minutes after waketime over which to ease out of the night values. 0 snaps straight to day.
+	#[serde(default)]
+	pub grace_minutes: f32,
+	/// Hours after waketime over which to ramp down from the night values, mirroring the evening ramp. 0 disables it.
+	#[serde(default)]
+	pub morning_n_hours: f32,
+	/// Keyframes for the whole day, which take over from the sections and ramps. See `curve::Curve` for the format
+	pub curve_file: Option<PathBuf>,
+	/// Shifts the whole schedule later, like `+30m`, or earlier, like `-1h`, without touching waketime
+	#[serde(default)]
+	pub schedule_offset: Offset,
+	/// More wake periods in the day, like `{ waketime = "15:00", n_hours = 1 }` after a nap. The main one, from the command line or `waketime`, always counts
+	#[serde(default)]
+	pub wake_periods: Vec<WakePeriod>,
+	/// Redshift, from 0 to 20, the evening ramp ends on
+	#[serde(default = "AppConfig::default_redshift")]
+	pub evening_end_redshift: f32,
+	/// Redshift, from 0 to 20, held through the night. Can differ from `evening_end_redshift` to settle further after dusk
+	#[serde(default = "AppConfig::default_redshift")]
+	pub night_redshift: f32,
+	/// Redshift, from -20 to 20, the morning and day settle on. Above 0 keeps the day a little warm and dim, below 0 cools past the day temperature, towards `cool_max`
+	#[serde(default)]
+	pub day_redshift: f32,
+	/// Kelvin added to whatever is set, presets and overrides included, for nudging everything warmer (negative) or cooler without touching the ranges
+	#[serde(default)]
+	pub temperature_bias: f32,
+	/// Multiplies whatever brightness is set, like 0.9 for everything 10% dimmer
+	#[serde(default = "AppConfig::default_brightness_scale")]
+	pub brightness_scale: f32,
+	/// Temperature reached at a `day_redshift` of -20. Defaults to the day end of the range, for no cooling. `[limits]` still hold
+	pub cool_max: Option<f32>,
+	/// Brightness goes up towards the night instead of down. Also set by `--invert-brightness`
+	#[serde(default)]
+	pub invert_brightness: bool,
+	/// Max random offset in Kelvin applied to the temperature each cycle; 0 to disable
+	#[serde(default)]
+	pub dither: f32,
+	/// Temperature is snapped to the nearest multiple of this many Kelvin, for panels that flicker on odd steps. 1 leaves it as is
+	#[serde(default = "AppConfig::default_temperature_quantum")]
+	pub temperature_quantum: f32,
+	/// How many Kelvin further than `temperature_range` the warm end goes with `auto_redshift extreme` on. `[limits]` still hold
+	#[serde(default = "AppConfig::default_extreme_extra_kelvin")]
+	pub extreme_extra_kelvin: f32,
+	#[serde(default)]
+	pub backend: BackendKind,
+	/// 1D lut, as a `.cube` or lines of `r g b` from 0 to 1, for gamma to be built on top of. Needs the `lut` feature and the wayland backend
+	pub lut: Option<PathBuf>,
+	/// Output names to apply gamma to, as regexes; all if empty. Only the wayland and xrandr backends can tell outputs apart.
+	#[serde(default)]
+	pub outputs_include: Vec<String>,
+	/// Output names, as regexes, to leave alone even if included
+	#[serde(default)]
+	pub outputs_exclude: Vec<String>,
+	#[serde(default)]
+	pub gdbus: GdbusConfig,
+	#[serde(default)]
+	pub command: CommandConfig,
+	/// Each cycle moves only this share of the way to the new target, averaging out jitter. 1 follows the target exactly
+	#[serde(default = "AppConfig::default_smoothing")]
+	pub smoothing: f32,
+	/// Ease into the first values set after launch over this many seconds, instead of snapping to them. 0 to disable
+	#[serde(default)]
+	pub soft_start_s: f32,
+	/// Backend isn't written to more often than once per this many seconds. Whatever comes in sooner waits, and only the latest of it is applied. 0 to disable
+	#[serde(default)]
+	pub min_apply_interval_s: f32,
+	/// For how long, in seconds, to keep waiting on startup for the backend to come up, like when launched before the compositor is. 0 tries once
+	#[serde(default = "AppConfig::default_startup_timeout_s")]
+	pub startup_timeout_s: u64,
+	/// Set gamma again whenever outputs are plugged in or out. The wayland backend hears of it itself, the others poll sway every `poll_interval_s`
+	#[serde(default)]
+	pub watch_outputs: bool,
+	/// Don't write gamma while sway has every display powered down, and re-apply right as one comes back on
+	#[serde(default)]
+	pub dpms_aware: bool,
+	/// How often, in seconds, to check for fullscreen windows and preset changes between cycles
+	#[serde(default = "AppConfig::default_poll_interval_s")]
+	pub poll_interval_s: u64,
+	/// Fifo to take `n_hours = 5` or `waketime = 7:30` lines from while running, each picked up on the next cycle. Created if missing
+	pub tweak_fifo: Option<PathBuf>,
+	#[serde(default)]
+	pub ambient: Ambient,
+	#[serde(default)]
+	pub battery: Battery,
+	#[serde(default)]
+	pub backlight: BacklightConfig,
+	#[serde(default)]
+	pub limits: Limits,
+	/// Other devices, like a smart bulb, driven by the same schedule and set alongside the screen
+	#[serde(default)]
+	pub targets: Vec<Target>,
+	#[serde(default)]
+	pub fullscreen: Fullscreen,
+	/// Named settings that can be switched to with `auto_redshift preset <name>`, overriding the schedule until cleared
+	#[serde(default)]
+	pub presets: HashMap<String, DisplaySettings>,
+	#[serde(default)]
+	pub hooks: PerSection<PhaseHooks>,
+	/// Local time windows, like `"22:00-06:00"`, during which day values are held whatever the schedule says
+	#[serde(default)]
+	pub blackout: Vec<TimeWindow>,
+	#[serde(default)]
+	pub calendar: CalendarConfig,
+	/// Local time, like `"23:30"`, after which gamma is held as it is until waketime
+	pub freeze_after: Option<NaiveTime>,
+	/// Absolute values that replace the interpolated ones for the whole section
+	#[serde(default)]
+	pub overrides: PerSection<PhaseOverride>,
+	/// What each section gets to change. Whatever it can't is held where the section before left it
+	#[serde(default)]
+	pub controls: PerSection<Controls>,
+	/// Named sets of any of the above, one of which can be laid over the rest of the file with `--profile`. Not written back out.
+	#[serde(default, skip_serializing)]
+	pub profiles: HashMap<String, config::Value>,
 }
 
-#[derive(Clone, Debug, Default, derive_new::new, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, derive_new::new, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Wallpapers {
+	/// Where relative section paths are looked up; can be left out if all of them are absolute
+	#[serde(default)]
 	pub root: PathBuf,
 	pub morning: String,
 	pub day: String,
 	pub evening: String,
 	pub night: String,
+	/// How images that don't match the output's aspect ratio are fit onto it
+	#[serde(default)]
+	#[new(default)]
+	pub mode: WallpaperMode,
+	/// Darken the wallpaper along with the redshift, down to this fraction of its brightness at full redshift, like 0.6. Needs imagemagick
+	#[new(default)]
+	pub dim: Option<f32>,
+	/// Minutes to fade from one section's wallpaper into the next over, starting as the section changes. Needs the `crossfade` feature
+	#[serde(default)]
+	#[new(default)]
+	pub crossfade_minutes: f32,
+}
+impl Wallpapers {
+	pub fn is_configured(&self) -> bool {
+		[&self.morning, &self.day, &self.evening, &self.night].iter().all(|w| !w.is_empty())
+	}
+
+	/// Absolute paths are taken as is, relative ones are under `root`
+	pub fn path(&self, section: DaySection) -> PathBuf {
+		let wallpaper = Path::new(match section {
+			DaySection::Morning => &self.morning,
+			DaySection::Day => &self.day,
+			DaySection::Evening => &self.evening,
+			DaySection::Night => &self.night,
+		});
+		match wallpaper.is_absolute() {
+			true => wallpaper.to_path_buf(),
+			false => self.root.join(wallpaper),
+		}
+	}
+
+	pub fn command(&self, section: DaySection) -> String {
+		self.command_for(&self.path(section))
+	}
+
+	pub fn command_for(&self, path: &Path) -> String {
+		format!("swaymsg output '*' bg {} {}", path.display(), self.mode)
+	}
+}
+
+/// As taken by `swaymsg output <name> bg <file> <mode>`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WallpaperMode {
+	#[default]
+	Fill,
+	Fit,
+	Stretch,
+	Center,
+	Tile,
+}
+impl fmt::Display for WallpaperMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Self::Fill => "fill",
+			Self::Fit => "fit",
+			Self::Stretch => "stretch",
+			Self::Center => "center",
+			Self::Tile => "tile",
+		};
+		f.write_str(s)
+	}
+}
+
+/// Named `temperature_range` presets, after the light source the warm end imitates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorProfile {
+	/// 1900K..6500K
+	Candle,
+	/// 2700K..6500K
+	Incandescent,
+	/// 3400K..6500K
+	Halogen,
+	/// 5000K..6500K
+	Daylight,
+}
+impl ColorProfile {
+	pub fn temperature_range(&self) -> (usize, usize) {
+		match self {
+			Self::Candle => (1900, 6500),
+			Self::Incandescent => (2700, 6500),
+			Self::Halogen => (3400, 6500),
+			Self::Daylight => (5000, 6500),
+		}
+	}
+}
+
+/// Warm end of the range as a share of the way from the day temperature down to `min`
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RelativeTemperature {
+	/// Kelvin, held through the day
+	pub day: usize,
+	/// Kelvin, the warmest we'd ever go
+	pub min: usize,
+	/// 0 stays at `day`, 100 goes all the way down to `min`
+	pub warm_percent: f32,
+}
+impl RelativeTemperature {
+	pub fn resolve(&self) -> (f32, f32) {
+		let (day, min) = (self.day as f32, self.min as f32);
+		(day - (day - min) * self.warm_percent.clamp(0.0, 100.0) / 100.0, day)
+	}
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SeasonalHours {
+	/// Summer is April through September
+	Seasons { summer: f32, winter: f32 },
+	/// Keyed by month number (`1`..`12`) or its three-letter name (`jan`..`dec`); unlisted months fall back to `--n_hours`
+	Months(HashMap<String, f32>),
+}
+impl SeasonalHours {
+	pub fn for_month(&self, month: u32) -> Option<f32> {
+		match self {
+			Self::Seasons { summer, winter } => match month {
+				4..=9 => Some(*summer),
+				_ => Some(*winter),
+			},
+			Self::Months(months) => months.iter().find(|(k, _)| parse_month(k) == Some(month)).map(|(_, v)| *v),
+		}
+	}
+}
+
+fn parse_month(s: &str) -> Option<u32> {
+	const NAMES: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+	let s = s.trim().to_lowercase();
+	match s.parse::<u32>() {
+		Ok(n) if (1..=12).contains(&n) => Some(n),
+		Ok(_) => None,
+		Err(_) => NAMES.iter().position(|name| s.starts_with(name)).map(|i| i as u32 + 1),
+	}
+}
+
+/// What sets the gamma
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+	/// wlr-gamma-service, over dbus
+	#[default]
+	Gdbus,
+	/// wlr-gamma-control directly, holding the connection open. Needs the `wayland` feature.
+	Wayland,
+	/// X11, through the `xrandr` cli
+	Xrandr,
+	/// GNOME's own night light, through `gsettings`. Temperature only
+	Gnome,
+	/// Whatever `[command]` says to run
+	Command,
+}
+
+/// Commands for `backend = "command"`, run without a shell
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CommandConfig {
+	/// Has to take `{temperature}`; without `{brightness}`, brightness is left alone
+	#[serde(default)]
+	pub set_command: String,
+	/// Should print the current temperature. The last one set is assumed if not given
+	pub get_temperature_command: Option<String>,
+	/// Should print the current brightness, from 0 to 1. The last one set is assumed if not given
+	pub get_brightness_command: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GdbusConfig {
+	/// Passed to `gdbus --timeout`; a call that hits it is retried like any other failure
+	#[serde(default = "GdbusConfig::default_timeout_s")]
+	pub timeout_s: u64,
+	#[serde(default = "GdbusConfig::default_retries")]
+	pub retries: u32,
+	/// Recorded replies to answer with instead of calling gdbus, for reproducing how odd output of the service is handled. See `tests/fixtures/gdbus`
+	pub replay: Option<PathBuf>,
+}
+impl GdbusConfig {
+	fn default_timeout_s() -> u64 {
+		5
+	}
+
+	fn default_retries() -> u32 {
+		2
+	}
+}
+impl Default for GdbusConfig {
+	fn default() -> Self {
+		Self {
+			timeout_s: Self::default_timeout_s(),
+			retries: Self::default_retries(),
+			replay: None,
+		}
+	}
+}
+
+/// Blend in brightness suggested by an ambient light sensor
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ambient {
+	#[serde(default)]
+	pub enabled: bool,
+	/// iio device directory, eg `/sys/bus/iio/devices/iio:device0`. First one with an illuminance channel if not given.
+	pub device: Option<PathBuf>,
+	/// 0 is schedule only, 1 is sensor only
+	#[serde(default = "Ambient::default_weight")]
+	pub weight: f32,
+	/// Illuminance at and above which the sensor asks for full brightness
+	#[serde(default = "Ambient::default_max_lux")]
+	pub max_lux: f32,
+}
+impl Ambient {
+	fn default_weight() -> f32 {
+		0.5
+	}
+
+	fn default_max_lux() -> f32 {
+		500.0
+	}
+}
+impl Default for Ambient {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			device: None,
+			weight: Self::default_weight(),
+			max_lux: Self::default_max_lux(),
+		}
+	}
+}
+
+/// Dim further while running low on battery
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Battery {
+	#[serde(default)]
+	pub enabled: bool,
+	/// power_supply directory, eg `/sys/class/power_supply/BAT0`. First battery if not given.
+	pub device: Option<PathBuf>,
+	/// Charge, in percent, at and below which brightness is scaled down while discharging
+	#[serde(default = "Battery::default_below_percent")]
+	pub below_percent: f32,
+	/// What brightness is multiplied by then
+	#[serde(default = "Battery::default_factor")]
+	pub factor: f32,
+}
+impl Battery {
+	fn default_below_percent() -> f32 {
+		20.0
+	}
+
+	fn default_factor() -> f32 {
+		0.7
+	}
+}
+impl Default for Battery {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			device: None,
+			below_percent: Self::default_below_percent(),
+			factor: Self::default_factor(),
+		}
+	}
+}
+
+/// Anything that can be set through a command
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+	/// Run every time the screen is set, with `{temperature}` and `{brightness}` filled in
+	pub set_command: String,
+}
+
+/// Drive the backlight through `brightnessctl` for brightness, instead of the gamma ramp
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BacklightConfig {
+	#[serde(default)]
+	pub enabled: bool,
+	/// As named in `/sys/class/backlight`; picked by brightnessctl if not given
+	pub device: Option<String>,
+	/// Fade to each new level over this long, if `brillo` is available
+	#[serde(default)]
+	pub brightness_fade_ms: u64,
+}
+
+/// Hold day values through busy events of a calendar, so that the screen looks normal in meetings. Needs the `calendar` feature.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+	/// Path or http(s) url of an ics file; off if not given
+	pub source: Option<String>,
+	#[serde(default = "CalendarConfig::default_refresh_minutes")]
+	pub refresh_minutes: u64,
+}
+impl CalendarConfig {
+	fn default_refresh_minutes() -> u64 {
+		30
+	}
+}
+impl Default for CalendarConfig {
+	fn default() -> Self {
+		Self {
+			source: None,
+			refresh_minutes: Self::default_refresh_minutes(),
+		}
+	}
+}
+
+/// Hard bounds on what is ever sent to a backend, whatever the schedule, presets or overrides come up with
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+	#[serde(default = "Limits::default_temperature")]
+	pub temperature: (f32, f32),
+	#[serde(default = "Limits::default_brightness")]
+	pub brightness: (f32, f32),
+}
+impl Limits {
+	fn default_temperature() -> (f32, f32) {
+		(1000.0, 6500.0)
+	}
+
+	fn default_brightness() -> (f32, f32) {
+		(0.1, 1.0)
+	}
+}
+impl Default for Limits {
+	fn default() -> Self {
+		Self {
+			temperature: Self::default_temperature(),
+			brightness: Self::default_brightness(),
+		}
+	}
+}
+
+/// Suspend redshift while a fullscreen window is present
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fullscreen {
+	#[serde(default)]
+	pub enabled: bool,
+	/// Should print a json tree of windows; any node with non-zero `fullscreen_mode` or truthy `fullscreen` counts
+	#[serde(default = "Fullscreen::default_detect_command")]
+	pub detect_command: String,
+}
+impl Fullscreen {
+	fn default_detect_command() -> String {
+		"swaymsg -t get_tree".to_owned()
+	}
+}
+impl Default for Fullscreen {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			detect_command: Self::default_detect_command(),
+		}
+	}
+}
+
+/// One value for each of the day sections
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PerSection<T> {
+	#[serde(default)]
+	pub morning: T,
+	#[serde(default)]
+	pub day: T,
+	#[serde(default)]
+	pub evening: T,
+	#[serde(default)]
+	pub night: T,
+}
+impl<T> PerSection<T> {
+	pub fn get(&self, section: DaySection) -> &T {
+		match section {
+			DaySection::Morning => &self.morning,
+			DaySection::Day => &self.day,
+			DaySection::Evening => &self.evening,
+			DaySection::Night => &self.night,
+		}
+	}
+}
+
+/// Shell commands ran once on transitions between day sections
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseHooks {
+	pub on_enter: Option<String>,
+	pub on_exit: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseOverride {
+	pub temperature: Option<f32>,
+	pub brightness: Option<f32>,
+}
+impl PhaseOverride {
+	pub fn is_set(&self) -> bool {
+		self.temperature.is_some() || self.brightness.is_some()
+	}
+}
+
+/// What a section changes, all of it by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Controls {
+	#[serde(default = "Controls::default_enabled")]
+	pub temperature: bool,
+	#[serde(default = "Controls::default_enabled")]
+	pub brightness: bool,
+	#[serde(default = "Controls::default_enabled")]
+	pub wallpaper: bool,
+}
+impl Controls {
+	fn default_enabled() -> bool {
+		true
+	}
+}
+impl Default for Controls {
+	fn default() -> Self {
+		Self {
+			temperature: Self::default_enabled(),
+			brightness: Self::default_enabled(),
+			wallpaper: Self::default_enabled(),
+		}
+	}
+}
+
+fn waketime_from_command(command: &str) -> Result<Waketime> {
+	let output = crate::cmd(command);
+	ensure!(
+		output.status.success(),
+		"`{command}` {}: {}",
+		output.status,
+		String::from_utf8_lossy(&output.stderr).trim()
+	);
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	stdout.trim().parse::<Waketime>().with_context(|| format!("bad output of `{command}`"))
 }
 
 fn deserialize_tuple<'de, D, T>(deserializer: D) -> Result<(T, T), D::Error>
@@ -33,14 +635,312 @@ where
 	Ok((vec[0], vec[1]))
 }
 
+/// Kelvin, or a color to take them from
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Temperature {
+	Kelvin(usize),
+	Color(String),
+}
+
+fn deserialize_temperature_range<'de, D>(deserializer: D) -> Result<(usize, usize), D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let kelvin = |t: Temperature| match t {
+		Temperature::Kelvin(k) => Ok(k),
+		Temperature::Color(color) => crate::color::to_kelvin(&color).map(|k| k.round() as usize).map_err(serde::de::Error::custom),
+	};
+	let vec = Vec::<Temperature>::deserialize(deserializer)?;
+	let [min, max]: [Temperature; 2] = vec.try_into().map_err(|_| serde::de::Error::custom("Expected a tuple of 2 elements"))?;
+	Ok((kelvin(min)?, kelvin(max)?))
+}
+
 impl AppConfig {
+	fn default_brightness_range() -> (f32, f32) {
+		(0.5, 1.0)
+	}
+
+	fn default_temperature_range() -> (usize, usize) {
+		(2500, 6500)
+	}
+
+	fn default_poll_interval_s() -> u64 {
+		5
+	}
+
+	fn default_brightness_scale() -> f32 {
+		1.0
+	}
+
+	fn default_startup_timeout_s() -> u64 {
+		30
+	}
+
+	fn default_redshift() -> f32 {
+		20.0
+	}
+
+	fn default_smoothing() -> f32 {
+		1.0
+	}
+
+	fn default_temperature_quantum() -> f32 {
+		1.0
+	}
+
+	fn default_extreme_extra_kelvin() -> f32 {
+		500.0
+	}
+
+	/// Warm and day ends of the temperature range, in Kelvin, whichever way they were given
+	pub fn temperature_bounds(&self) -> (f32, f32) {
+		match self.relative_temperature {
+			Some(relative) => relative.resolve(),
+			None => (self.temperature_range.0 as f32, self.temperature_range.1 as f32),
+		}
+	}
+
+	/// Coolest the schedule goes, with a negative `day_redshift`
+	pub fn cool_max(&self) -> f32 {
+		self.cool_max.unwrap_or(self.temperature_bounds().1)
+	}
+
 	//TODO!!!: figure out how to return error iff all potentail sources combined fail to provide all of the values;
-	pub fn read(path: &Path) -> Result<Self> {
-		let builder = config::Config::builder().add_source(config::File::from(path.to_path_buf()));
+	/// `color_profile` takes precedence over the one in the file; an explicit `temperature_range` beats both.
+	/// Keys of the selected `profile` replace the top-level ones, tables are merged key by key.
+	pub fn read(path: &Path, color_profile: Option<ColorProfile>, profile: Option<&str>) -> Result<Self, Error> {
+		Self::load(path, color_profile, profile)
+			.with_context(|| format!("failed to read config at {}", path.display()))
+			.map_err(Error::Config)
+	}
+
+	fn load(path: &Path, color_profile: Option<ColorProfile>, profile: Option<&str>) -> Result<Self> {
+		let mut builder = config::Config::builder().add_source(config::File::from(path.to_path_buf()));
+
+		if let Some(name) = profile {
+			// keys are lowercased on read
+			let name = name.to_lowercase();
+			let overlay: config::Map<String, config::Value> = builder
+				.build_cloned()?
+				.get(&format!("profiles.{name}"))
+				.map_err(|_| anyhow!("no `[profiles.{name}]` in {}", path.display()))?;
+			builder = builder.add_source(Overlay(overlay));
+		}
+
+		let explicit = builder.build_cloned()?;
+		let given = |key: &str| explicit.get::<config::Value>(key).is_ok();
+		for (range, day, night) in [
+			("temperature_range", "temp-day", "temp-night"),
+			("brightness_range", "brightness-day", "brightness-night"),
+		] {
+			if given(range) && (given(day) || given(night)) {
+				bail!("both `{range}` and `{day}`/`{night}` are given; keep one");
+			}
+		}
+
+		let color_profile = color_profile.or_else(|| builder.build_cloned().ok()?.get::<ColorProfile>("color_profile").ok());
+		if let Some(profile) = color_profile {
+			let (min, max) = profile.temperature_range();
+			builder = builder
+				.set_default("temperature_range", vec![min as i64, max as i64])?
+				.set_override("color_profile", format!("{profile:?}").to_lowercase())?;
+		}
 
 		let settings: config::Config = builder.build()?;
-		let settings: Self = settings.try_deserialize()?;
+		let mut settings: Self = settings.try_deserialize().map_err(|e| match suggest_field(&e.to_string()) {
+			Some(suggestion) => anyhow!(suggestion),
+			None => e.into(),
+		})?;
+		if let Some(day) = settings.temp_day {
+			settings.temperature_range.1 = day;
+		}
+		if let Some(night) = settings.temp_night {
+			settings.temperature_range.0 = night;
+		}
+		if let Some(day) = settings.brightness_day {
+			settings.brightness_range.1 = day;
+		}
+		if let Some(night) = settings.brightness_night {
+			settings.brightness_range.0 = night;
+		}
+		settings.validate()?;
 
 		Ok(settings)
 	}
+
+	/// From `waketime_command` if there is one and it works, `waketime` otherwise
+	pub fn resolve_waketime(&self) -> Option<Waketime> {
+		let from_command = self.waketime_command.as_deref().and_then(|command| {
+			waketime_from_command(command)
+				.inspect_err(|e| tracing::warn!("falling back on the static waketime: {e:#}"))
+				.ok()
+		});
+		from_command.or(self.waketime)
+	}
+
+	pub fn builder() -> AppConfigBuilder {
+		AppConfigBuilder::default()
+	}
+
+	/// Catches values that would otherwise only blow up, or quietly misbehave, once the daemon is running
+	pub fn validate(&self) -> Result<()> {
+		let (b_min, b_max) = self.brightness_range;
+		ensure!(
+			0.0 <= b_min && b_min <= b_max,
+			"brightness_range has to be [min, max] with 0 <= min <= max, got {:?}",
+			self.brightness_range
+		);
+		let (t_min, t_max) = self.temperature_bounds();
+		ensure!(
+			0.0 < t_min && t_min <= t_max,
+			"temperature range has to be [min, max] with 0 < min <= max, got [{t_min}, {t_max}]"
+		);
+		ensure!(
+			self.cool_max() >= t_max,
+			"cool_max can't be warmer than the day end of the temperature range, got {} below {t_max}",
+			self.cool_max()
+		);
+		ensure!(
+			(-20.0..=20.0).contains(&self.day_redshift),
+			"day_redshift has to be in [-20, 20], got {}",
+			self.day_redshift
+		);
+		ensure!(
+			self.smoothing > 0.0 && self.smoothing <= 1.0,
+			"smoothing has to be in (0, 1], 1 being no smoothing, got {}",
+			self.smoothing
+		);
+		ensure!(
+			self.temperature_bias.is_finite(),
+			"temperature_bias has to be a number, got {}",
+			self.temperature_bias
+		);
+		ensure!(
+			self.brightness_scale.is_finite() && self.brightness_scale > 0.0,
+			"brightness_scale has to be positive, got {}",
+			self.brightness_scale
+		);
+		if let Some(dim) = self.wallpapers.dim {
+			ensure!((0.0..=1.0).contains(&dim), "wallpapers.dim has to be in [0, 1], got {dim}");
+		}
+		let seasonal = match &self.n_hours {
+			Some(SeasonalHours::Seasons { summer, winter }) => vec![*summer, *winter],
+			Some(SeasonalHours::Months(months)) => months.values().copied().collect(),
+			None => Vec::new(),
+		};
+		for hours in seasonal.into_iter().chain(self.wake_periods.iter().map(|p| p.n_hours)) {
+			ensure!(
+				hours.is_finite() && hours > 0.0,
+				"evening ramps have to be longer than 0 hours, got n_hours = {hours}"
+			);
+		}
+		ensure!(
+			self.morning_n_hours.is_finite() && self.morning_n_hours >= 0.0,
+			"morning_n_hours has to be 0 (off) or more, got {}",
+			self.morning_n_hours
+		);
+		OutputFilter::new(&self.outputs_include, &self.outputs_exclude)?;
+		ensure!(
+			self.temperature_quantum > 0.0,
+			"temperature_quantum has to be positive, got {}",
+			self.temperature_quantum
+		);
+		for (name, (min, max)) in [("temperature", self.limits.temperature), ("brightness", self.limits.brightness)] {
+			ensure!(
+				0.0 <= min && min <= max,
+				"limits.{name} has to be [min, max] with 0 <= min <= max, got [{min}, {max}]"
+			);
+		}
+		if self.backend == BackendKind::Command {
+			check_placeholders(&self.command.set_command, &["temperature"]).context("bad `command.set_command`")?;
+		}
+		for (i, target) in self.targets.iter().enumerate() {
+			check_placeholders(&target.set_command, &[]).with_context(|| format!("bad `set_command` of target {i}"))?;
+		}
+		Ok(())
+	}
+}
+/// What an empty config file comes down to
+impl Default for AppConfig {
+	fn default() -> Self {
+		let empty = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(std::iter::empty::<(&str, &str)>());
+		Self::deserialize(empty).expect("every field has a default")
+	}
+}
+
+/// Puts together an [`AppConfig`] in code, for when there is no file to read it from. Starts off from the defaults.
+#[derive(Clone, Debug, Default)]
+pub struct AppConfigBuilder {
+	config: AppConfig,
+}
+impl AppConfigBuilder {
+	pub fn brightness_range(mut self, min: f32, max: f32) -> Self {
+		self.config.brightness_range = (min, max);
+		self
+	}
+
+	pub fn temperature_range(mut self, min: usize, max: usize) -> Self {
+		self.config.temperature_range = (min, max);
+		self
+	}
+
+	/// Also sets `temperature_range` to the profile's, like it would be in a file without one
+	pub fn color_profile(mut self, profile: ColorProfile) -> Self {
+		self.config.color_profile = Some(profile);
+		self.config.temperature_range = profile.temperature_range();
+		self
+	}
+
+	pub fn wallpapers(mut self, wallpapers: Wallpapers) -> Self {
+		self.config.wallpapers = wallpapers;
+		self
+	}
+
+	pub fn build(self) -> Result<AppConfig> {
+		self.config.validate()?;
+		Ok(self.config)
+	}
+}
+
+/// Table already pulled out of the file, to be laid over it
+#[derive(Clone, Debug)]
+struct Overlay(config::Map<String, config::Value>);
+impl config::Source for Overlay {
+	fn clone_into_box(&self) -> Box<dyn config::Source + Send + Sync> {
+		Box::new(self.clone())
+	}
+
+	fn collect(&self) -> Result<config::Map<String, config::Value>, config::ConfigError> {
+		Ok(self.0.clone())
+	}
+}
+
+/// Turns serde's "unknown field `x`, expected one of `a`, `b`" into a suggestion of the closest expected one, if any is close enough to be a typo
+fn suggest_field(error: &str) -> Option<String> {
+	let rest = error.split_once("unknown field `")?.1;
+	let (unknown, expected) = rest.split_once('`')?;
+	let closest = expected
+		.split('`')
+		.skip(1)
+		.step_by(2)
+		.map(|candidate| (levenshtein(unknown, candidate), candidate))
+		.min()
+		.filter(|(distance, _)| *distance <= (unknown.len() / 3).max(2))?;
+	Some(format!("unknown field `{unknown}`, did you mean `{}`?", closest.1))
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut diagonal = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = diagonal + (ca != *cb) as usize;
+			diagonal = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+		}
+	}
+	row[b.len()]
 }
@@ -0,0 +1,100 @@
+use std::{
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use image::{imageops::FilterType, RgbaImage};
+
+/// How many images a fade goes through, the last one being the new wallpaper itself
+pub const STEPS: u32 = 10;
+
+/// Fraction of the way into the fade after `elapsed`, in whole steps of `1 / STEPS`
+pub fn ratio(elapsed: Duration, window: Duration) -> f32 {
+	if window.is_zero() {
+		return 1.0;
+	}
+	let fraction = (elapsed.as_secs_f32() / window.as_secs_f32()).clamp(0.0, 1.0);
+	(fraction * STEPS as f32).floor() / STEPS as f32
+}
+
+/// `from` mixed into `to`, at `ratio` of the way to `to`, written under the temp dir. `from` is stretched over `to` if their sizes differ
+pub fn blend(from: &Path, to: &Path, ratio: f32) -> Result<PathBuf> {
+	let percent = (ratio.clamp(0.0, 1.0) * 100.0).round() as u32;
+	let stem = |p: &Path| p.file_stem().map_or_else(String::new, |s| s.to_string_lossy().into_owned());
+	let dir = std::env::temp_dir().join("auto_redshift").join("crossfade");
+	let out = dir.join(format!(
+		"{}-{}-{:016x}-{percent}.png",
+		stem(from),
+		stem(to),
+		crate::wallpaper::cache_key(&[from, to])
+	));
+	if out.exists() {
+		return Ok(out);
+	}
+	let open = |p: &Path| -> Result<RgbaImage> { Ok(image::open(p).with_context(|| format!("failed to open {}", p.display()))?.into_rgba8()) };
+	let to_image = open(to)?;
+	let mut from_image = open(from)?;
+	if from_image.dimensions() != to_image.dimensions() {
+		from_image = image::imageops::resize(&from_image, to_image.width(), to_image.height(), FilterType::Triangle);
+	}
+	let t = percent as f32 / 100.0;
+	let mut blended = to_image;
+	for (to_pixel, from_pixel) in blended.pixels_mut().zip(from_image.pixels()) {
+		for (to_channel, from_channel) in to_pixel.0.iter_mut().zip(from_pixel.0) {
+			*to_channel = (from_channel as f32 * (1.0 - t) + *to_channel as f32 * t).round() as u8;
+		}
+	}
+	std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+	blended.save(&out).with_context(|| format!("failed to write {}", out.display()))?;
+	Ok(out)
+}
+
+/// Going from one wallpaper to the next over `window`
+#[derive(Clone, Debug)]
+pub struct Crossfade {
+	from: PathBuf,
+	to: PathBuf,
+	started: Instant,
+	window: Duration,
+	/// Step last handed out
+	shown: Option<u32>,
+}
+impl Crossfade {
+	pub fn new(from: PathBuf, to: PathBuf, window: Duration) -> Self {
+		Self {
+			from,
+			to,
+			started: Instant::now(),
+			window,
+			shown: None,
+		}
+	}
+
+	/// How often there is a new step to show
+	pub fn tick(&self) -> Duration {
+		self.window / STEPS
+	}
+
+	/// Image for the step the fade is at now, if it's not the one handed out last
+	pub fn step(&mut self) -> Option<Result<PathBuf>> {
+		let ratio = ratio(self.started.elapsed(), self.window);
+		let step = (ratio * STEPS as f32).round() as u32;
+		if self.shown == Some(step) {
+			return None;
+		}
+		self.shown = Some(step);
+		Some(match step >= STEPS {
+			true => Ok(self.to.clone()),
+			false => blend(&self.from, &self.to, ratio),
+		})
+	}
+
+	pub fn is_done(&self) -> bool {
+		self.shown.is_some_and(|step| step >= STEPS)
+	}
+
+	pub fn to(&self) -> &Path {
+		&self.to
+	}
+}
@@ -0,0 +1,104 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use chrono::{NaiveTime, Timelike};
+use serde::Deserialize;
+
+use crate::display::DisplaySettings;
+
+/// Point of the day to pass through, with values in between interpolated linearly
+#[derive(Clone, Debug, PartialEq)]
+pub struct Keyframe {
+	pub time: NaiveTime,
+	pub settings: DisplaySettings,
+	/// Set when this keyframe is reached, and held until the next one that has one. Relative paths are under `wallpapers.root`
+	pub wallpaper: Option<PathBuf>,
+}
+
+/// Whole day given as keyframes, in place of the sections and ramps. After the last keyframe it goes on towards the first one, past midnight, so any of them cover the full 24h.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Curve {
+	keyframes: Vec<Keyframe>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCurve {
+	keyframe: Vec<RawKeyframe>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawKeyframe {
+	time: String,
+	temperature: f32,
+	brightness: f32,
+	wallpaper: Option<PathBuf>,
+}
+
+impl Curve {
+	pub fn read(path: &Path) -> Result<Self> {
+		let s = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+		Self::parse(&s).with_context(|| format!("bad curve in {}", path.display()))
+	}
+
+	/// Takes `[[keyframe]]` tables of `time = "HH:MM"`, `temperature`, `brightness` and optionally `wallpaper`, sorted by time
+	pub fn parse(s: &str) -> Result<Self> {
+		let raw: RawCurve = config::Config::builder()
+			.add_source(config::File::from_str(s, config::FileFormat::Toml))
+			.build()?
+			.try_deserialize()?;
+		let keyframes = raw
+			.keyframe
+			.into_iter()
+			.map(|k| {
+				let time = NaiveTime::parse_from_str(&k.time, "%H:%M").map_err(|e| anyhow!("bad keyframe time {:?}: {e}, expected HH:MM", k.time))?;
+				Ok(Keyframe {
+					time,
+					settings: DisplaySettings::new(k.temperature, k.brightness),
+					wallpaper: k.wallpaper,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+		Self::new(keyframes)
+	}
+
+	pub fn new(keyframes: Vec<Keyframe>) -> Result<Self> {
+		ensure!(!keyframes.is_empty(), "needs at least one keyframe");
+		for pair in keyframes.windows(2) {
+			ensure!(
+				pair[0].time < pair[1].time,
+				"keyframes have to be sorted by time, with none repeated, but {} comes after {}",
+				pair[1].time.format("%H:%M"),
+				pair[0].time.format("%H:%M")
+			);
+		}
+		Ok(Self { keyframes })
+	}
+
+	/// Keyframes either side of `now`, and how far along from the first to the second it is
+	fn around(&self, now: NaiveTime) -> (&Keyframe, &Keyframe, f32) {
+		let day_s = |t: NaiveTime| t.num_seconds_from_midnight() as i64;
+		let next = self.keyframes.iter().position(|k| k.time > now).unwrap_or(0);
+		let (from, to) = (&self.keyframes[(next + self.keyframes.len() - 1) % self.keyframes.len()], &self.keyframes[next]);
+		let span = (day_s(to.time) - day_s(from.time)).rem_euclid(24 * 3600);
+		let progress = (day_s(now) - day_s(from.time)).rem_euclid(24 * 3600);
+		let t = match span {
+			0 => 0.0,
+			span => progress as f32 / span as f32,
+		};
+		(from, to, t)
+	}
+
+	pub fn at(&self, now: NaiveTime) -> DisplaySettings {
+		let (from, to, t) = self.around(now);
+		from.settings.lerp(to.settings, t)
+	}
+
+	/// Of the last keyframe with a wallpaper at or before `now`, wrapping past midnight
+	pub fn wallpaper_at(&self, now: NaiveTime) -> Option<&Path> {
+		let passed = self.keyframes.iter().rposition(|k| k.time <= now).unwrap_or(self.keyframes.len() - 1);
+		let (before, after) = self.keyframes.split_at(passed + 1);
+		before.iter().rev().chain(after.iter().rev()).find_map(|k| k.wallpaper.as_deref())
+	}
+}
@@ -0,0 +1,104 @@
+use std::sync::{
+	atomic::{AtomicBool, Ordering},
+	Arc,
+};
+
+use anyhow::{Context, Result};
+use zbus::blocking::{connection, Connection};
+
+use crate::{display::DisplaySettings, schedule::DaySection};
+
+pub const NAME: &str = "org.auto_redshift";
+pub const PATH: &str = "/org/auto_redshift";
+
+struct Interface {
+	temperature: f64,
+	brightness: f64,
+	section: String,
+	reset: Arc<AtomicBool>,
+}
+#[zbus::interface(name = "org.auto_redshift")]
+impl Interface {
+	/// 0 until something is applied
+	#[zbus(property)]
+	fn temperature(&self) -> f64 {
+		self.temperature
+	}
+
+	#[zbus(property)]
+	fn brightness(&self) -> f64 {
+		self.brightness
+	}
+
+	/// Empty until the first cycle
+	#[zbus(property)]
+	fn section(&self) -> String {
+		self.section.clone()
+	}
+
+	/// Drops any preset, extreme mode and freeze, and re-applies the schedule on the next cycle
+	fn reset(&self) {
+		self.reset.store(true, Ordering::Relaxed);
+	}
+}
+
+/// State of the daemon, published on the session bus as `org.auto_redshift`
+pub struct Service {
+	connection: Connection,
+	reset: Arc<AtomicBool>,
+}
+impl Service {
+	pub fn register() -> Result<Self> {
+		let reset = Arc::new(AtomicBool::new(false));
+		let interface = Interface {
+			temperature: 0.0,
+			brightness: 0.0,
+			section: String::new(),
+			reset: reset.clone(),
+		};
+		let connection = connection::Builder::session()
+			.and_then(|b| b.name(NAME))
+			.and_then(|b| b.serve_at(PATH, interface))
+			.and_then(|b| b.build())
+			.with_context(|| format!("failed to register {NAME} on the session bus"))?;
+		Ok(Self { connection, reset })
+	}
+
+	/// Whether `Reset` was called since the last time this was asked
+	pub fn take_reset(&self) -> bool {
+		self.reset.swap(false, Ordering::Relaxed)
+	}
+
+	/// Sends `PropertiesChanged` for whatever differs from what was published before
+	pub fn publish_settings(&self, settings: DisplaySettings) -> Result<()> {
+		let iface = self.connection.object_server().interface::<_, Interface>(PATH)?;
+		let mut interface = iface.get_mut();
+		let emitter = iface.signal_emitter();
+		let (temperature, brightness) = (settings.temperature as f64, settings.brightness as f64);
+		if interface.temperature != temperature {
+			interface.temperature = temperature;
+			zbus::block_on(interface.temperature_changed(emitter))?;
+		}
+		if interface.brightness != brightness {
+			interface.brightness = brightness;
+			zbus::block_on(interface.brightness_changed(emitter))?;
+		}
+		Ok(())
+	}
+
+	pub fn publish_section(&self, section: DaySection) -> Result<()> {
+		let iface = self.connection.object_server().interface::<_, Interface>(PATH)?;
+		let mut interface = iface.get_mut();
+		let section = section.to_string();
+		if interface.section != section {
+			interface.section = section;
+			zbus::block_on(interface.section_changed(iface.signal_emitter()))?;
+		}
+		Ok(())
+	}
+}
+impl std::fmt::Debug for Service {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Service").field("reset", &self.reset).finish_non_exhaustive()
+	}
+}
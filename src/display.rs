@@ -0,0 +1,137 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+	config::{AppConfig, PhaseOverride},
+	schedule::DaySection,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, derive_new::new, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisplaySettings {
+	/// Kelvin
+	pub temperature: f32,
+	/// from 0 to 1
+	pub brightness: f32,
+}
+
+/// Compact `5000K@0.81` form, for flags and state files
+impl fmt::Display for DisplaySettings {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}K@{}", self.temperature, self.brightness)
+	}
+}
+impl FromStr for DisplaySettings {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		let (temperature, brightness) = s
+			.trim()
+			.split_once('@')
+			.ok_or_else(|| anyhow!("expected `<temperature>K@<brightness>`, like `5000K@0.81`, got {s:?}"))?;
+		let temperature = temperature.trim_end_matches(['K', 'k']);
+		let temperature: f32 = temperature.parse().with_context(|| format!("bad temperature {temperature:?}"))?;
+		let brightness: f32 = brightness.parse().with_context(|| format!("bad brightness {brightness:?}"))?;
+		let settings = Self { temperature, brightness };
+		settings.validate()?;
+		ensure!(brightness <= 1.0, "brightness must be within 0..=1, got {brightness}");
+		Ok(settings)
+	}
+}
+
+impl DisplaySettings {
+	/// Whether this can be sent to a backend at all. NaN and infinities have nowhere to come from but a bug upstream
+	pub fn validate(&self) -> Result<()> {
+		let Self { temperature, brightness } = *self;
+		ensure!(
+			temperature.is_finite() && temperature > 0.0,
+			"temperature must be a positive number of Kelvin, got {temperature}"
+		);
+		// config is free to take brightness past 1, for backends that can boost
+		ensure!(
+			brightness.is_finite() && brightness >= 0.0,
+			"brightness must be a non-negative number, got {brightness}"
+		);
+		Ok(())
+	}
+
+	pub fn overridden_by(self, o: &PhaseOverride) -> Self {
+		Self {
+			temperature: o.temperature.unwrap_or(self.temperature),
+			brightness: o.brightness.unwrap_or(self.brightness),
+		}
+	}
+
+	/// Stretches temperature over a range reaching `extra` Kelvin further on the warm end than `config`'s, with the cold end staying put
+	pub fn warmer_by(self, extra: f32, config: &AppConfig) -> Self {
+		let (t_min, t_max) = config.temperature_bounds();
+		let span = t_max - t_min;
+		let temperature = match span > 0.0 {
+			true => t_max - (t_max - self.temperature) * (span + extra) / span,
+			false => self.temperature - extra,
+		};
+		Self { temperature, ..self }
+	}
+
+	/// `t` of the way from `self` to `to`, 0 being `self` and 1 `to`
+	pub fn lerp(self, to: Self, t: f32) -> Self {
+		let t = t.clamp(0.0, 1.0);
+		Self {
+			temperature: self.temperature + (to.temperature - self.temperature) * t,
+			brightness: self.brightness + (to.brightness - self.brightness) * t,
+		}
+	}
+}
+
+/// What the schedule asks for, before any of the environment-dependent adjustments
+pub fn settings_for(section: DaySection, redshift: f32, config: &AppConfig) -> DisplaySettings {
+	calculate_display_settings(redshift, config).overridden_by(config.overrides.get(section))
+}
+
+/// Interpolates between the configured ranges: redshift 0 maps on the max of both, 20 on the min.
+/// Negative redshift goes on past the max temperature, up to `cool_max` at -20, with brightness staying at its max.
+pub fn calculate_display_settings(redshift: f32, config: &AppConfig) -> DisplaySettings {
+	let (t_min, _) = config.temperature_bounds();
+	let t_cool = config.cool_max();
+	let (b_min, b_max) = config.brightness_range;
+	let brightness_step = (b_max - b_min) / 20.0;
+
+	let temperature = redshift_to_temperature(redshift, config);
+	// snapping could otherwise land past either end of the range
+	let temperature = ((temperature / config.temperature_quantum).round() * config.temperature_quantum).clamp(t_min, t_cool);
+	let redshift = redshift.max(0.0);
+	// reflective and e-ink panels need more backlight at night, not less
+	let brightness = match config.invert_brightness {
+		true => b_min + redshift * brightness_step,
+		false => b_max - redshift * brightness_step,
+	};
+
+	DisplaySettings { temperature, brightness }
+}
+
+/// Kelvin that `redshift` maps on within `config`'s ranges, before snapping to `temperature_quantum`
+pub fn redshift_to_temperature(redshift: f32, config: &AppConfig) -> f32 {
+	let (t_min, t_max) = config.temperature_bounds();
+	let step = match redshift < 0.0 {
+		true => (config.cool_max() - t_max) / 20.0,
+		false => (t_max - t_min) / 20.0,
+	};
+	t_max - redshift * step
+}
+
+/// Inverse of `redshift_to_temperature`. Kelvin outside of the ranges are clamped into them first, and in a range of zero width any redshift would do, so 0 is returned.
+pub fn temperature_to_redshift(temperature: f32, config: &AppConfig) -> f32 {
+	let (t_min, t_max) = config.temperature_bounds();
+	let t_cool = config.cool_max();
+	let temperature = temperature.clamp(t_min, t_cool);
+	let span = match temperature > t_max {
+		true => t_cool - t_max,
+		false => t_max - t_min,
+	};
+	match span > 0.0 {
+		true => (t_max - temperature) / span * 20.0,
+		false => 0.0,
+	}
+}
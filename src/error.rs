@@ -0,0 +1,39 @@
+/// What went wrong, by who is to fix it. The details are further down the `source` chain.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	/// Config is missing, malformed, or lacks what the command needs
+	#[error("bad config")]
+	Config(#[source] anyhow::Error),
+	/// The gamma service didn't respond, or a command run on its behalf failed
+	#[error("backend failed")]
+	Backend(#[source] anyhow::Error),
+	/// A value given on the command line or over a fifo doesn't parse
+	#[error("bad {what} {input:?}: {reason}")]
+	Parse { what: &'static str, input: String, reason: String },
+	/// Something we need isn't installed, or wasn't built in
+	#[error("unsupported environment")]
+	Env(#[source] anyhow::Error),
+}
+impl Error {
+	pub fn parse(what: &'static str, input: &str, reason: impl ToString) -> Self {
+		Self::Parse {
+			what,
+			input: input.to_owned(),
+			reason: reason.to_string(),
+		}
+	}
+
+	/// For the process to exit with
+	pub fn exit_code(&self) -> u8 {
+		match self {
+			Self::Config(_) | Self::Parse { .. } => 2,
+			Self::Backend(_) => 3,
+			Self::Env(_) => 4,
+		}
+	}
+
+	/// Whether this says anything beyond the kind of failure. Those that don't can be left out when printing the chain.
+	pub fn is_wrapper(&self) -> bool {
+		!matches!(self, Self::Parse { .. })
+	}
+}
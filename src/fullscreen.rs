@@ -0,0 +1,27 @@
+use serde_json::Value;
+
+use crate::{cmd, config::Fullscreen};
+
+pub fn fullscreen_present(config: &Fullscreen) -> bool {
+	let output = cmd(&config.detect_command);
+	let Ok(tree) = serde_json::from_slice::<Value>(&output.stdout) else {
+		return false;
+	};
+	has_fullscreen_node(&tree)
+}
+
+/// Walks the whole tree, so works both on sway's `get_tree` (`fullscreen_mode: 0|1|2`) and on flat outputs like `hyprctl activewindow -j` (`fullscreen: bool`)
+pub fn has_fullscreen_node(tree: &Value) -> bool {
+	match tree {
+		Value::Object(map) => {
+			let flagged = match (map.get("fullscreen_mode"), map.get("fullscreen")) {
+				(Some(Value::Number(n)), _) | (_, Some(Value::Number(n))) => n.as_u64().is_some_and(|n| n > 0),
+				(_, Some(Value::Bool(b))) => *b,
+				_ => false,
+			};
+			flagged || map.values().any(has_fullscreen_node)
+		}
+		Value::Array(nodes) => nodes.iter().any(has_fullscreen_node),
+		_ => false,
+	}
+}
@@ -0,0 +1,29 @@
+use crate::{
+	cmd,
+	config::{PerSection, PhaseHooks},
+	schedule::DaySection,
+};
+
+/// Returns commands to run on `from -> to`, in order. On startup (`from == None`) only `on_enter` of the current section fires.
+pub fn transition_commands(hooks: &PerSection<PhaseHooks>, from: Option<DaySection>, to: DaySection) -> Vec<&str> {
+	if from == Some(to) {
+		return Vec::new();
+	}
+	let exit = from.and_then(|from| hooks.get(from).on_exit.as_deref());
+	let enter = hooks.get(to).on_enter.as_deref();
+	exit.into_iter().chain(enter).collect()
+}
+
+/// Hook failures are only reported, never propagated: a broken theme toggle shouldn't take the daemon down with it
+pub fn dispatch(hooks: &PerSection<PhaseHooks>, from: Option<DaySection>, to: DaySection) {
+	for command in transition_commands(hooks, from, to) {
+		let output = cmd(command);
+		if !output.status.success() {
+			tracing::warn!(
+				"hook `{command}` failed ({}): {}",
+				output.status,
+				String::from_utf8_lossy(&output.stderr).trim()
+			);
+		}
+	}
+}
@@ -1 +1,62 @@
+use std::{
+	ffi::OsStr,
+	os::unix::fs::PermissionsExt,
+	path::PathBuf,
+	process::{Command, Output},
+};
 
+use anyhow::{anyhow, Result};
+
+pub mod ambient;
+pub mod backend;
+pub mod battery;
+#[cfg(feature = "calendar")]
+pub mod calendar;
+pub mod calibrate;
+pub mod clock;
+pub mod color;
+pub mod config;
+#[cfg(feature = "crossfade")]
+pub mod crossfade;
+pub mod curve;
+#[cfg(feature = "dbus")]
+pub mod dbus;
+pub mod display;
+pub mod error;
+pub mod fullscreen;
+pub mod hooks;
+#[cfg(feature = "lut")]
+pub mod lut;
+pub mod outputs;
+pub mod profile;
+#[cfg(feature = "resume")]
+pub mod resume;
+pub mod schedule;
+pub mod service;
+pub mod state;
+pub mod tune;
+pub mod tweaks;
+pub mod wallpaper;
+#[cfg(feature = "watch-config")]
+pub mod watch;
+
+/// Looks `binary` up on `$PATH`, like `which` would
+pub fn which(binary: &str) -> Option<PathBuf> {
+	let path = std::env::var_os("PATH")?;
+	std::env::split_paths(&path)
+		.map(|dir| dir.join(binary))
+		.find(|candidate| candidate.metadata().is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0))
+}
+
+/// `hint` is what to tell the user to do about it, eg "install glib2"
+pub fn require_binary(binary: &str, hint: &str) -> Result<PathBuf> {
+	which(binary).ok_or_else(|| anyhow!("{binary} not found on PATH; {hint}"))
+}
+
+pub fn cmd<S>(command: S) -> Output
+where
+	S: AsRef<OsStr>,
+{
+	let output = Command::new("sh").arg("-c").arg(command).output().unwrap();
+	output
+}
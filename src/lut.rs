@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+
+/// 1D per-channel curve, like one from a calibrated ICC profile, for gamma to be built on top of
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut {
+	/// `[r, g, b]` from 0 to 1, evenly spaced over the input range
+	rows: Vec<[f32; 3]>,
+}
+impl Lut {
+	pub fn read(path: &Path) -> Result<Self> {
+		let s = std::fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+		Self::parse(&s).with_context(|| format!("bad lut in {}", path.display()))
+	}
+
+	/// Takes a 1D `.cube`, or just lines of `r g b`. `#` starts a comment.
+	pub fn parse(s: &str) -> Result<Self> {
+		let mut rows = Vec::new();
+		for line in s.lines().map(|l| l.split('#').next().unwrap_or_default().trim()).filter(|l| !l.is_empty()) {
+			// `.cube` keywords, like TITLE and LUT_1D_SIZE
+			if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+				if line.starts_with("LUT_3D_SIZE") {
+					bail!("3D luts can't be loaded into a gamma ramp");
+				}
+				continue;
+			}
+			let values: Vec<f32> = line
+				.split_whitespace()
+				.map(str::parse)
+				.collect::<Result<_, _>>()
+				.with_context(|| format!("bad row {line:?}"))?;
+			let row: [f32; 3] = values.try_into().map_err(|_| anyhow::anyhow!("expected `r g b`, got {line:?}"))?;
+			rows.push(row.map(|v| v.clamp(0.0, 1.0)));
+		}
+		ensure!(rows.len() >= 2, "needs at least 2 rows, got {}", rows.len());
+		Ok(Self { rows })
+	}
+
+	/// Value of `channel` (0 to 2, for r, g and b) at `x` from 0 to 1, interpolated between rows
+	pub fn sample(&self, channel: usize, x: f32) -> f32 {
+		let position = x.clamp(0.0, 1.0) * (self.rows.len() - 1) as f32;
+		let i = (position as usize).min(self.rows.len() - 2);
+		let t = position - i as f32;
+		self.rows[i][channel] * (1.0 - t) + self.rows[i + 1][channel] * t
+	}
+}
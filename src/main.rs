@@ -1,168 +1,1617 @@
+use anyhow::{anyhow, Context};
+use auto_redshift::{
+	ambient,
+	backend::{self, GammaBackend, NothingSet},
+	battery, calibrate,
+	clock::{self, Clock, SystemClock, VirtualClock},
+	cmd,
+	config::{AppConfig, ColorProfile},
+	curve::Curve,
+	display::{calculate_display_settings, settings_for, DisplaySettings},
+	error::Error,
+	fullscreen, hooks,
+	outputs::OutputFilter,
+	profile::OutputFormat,
+	require_binary,
+	schedule::{evaluate_time, sections_between, DaySection, RedshiftLevels, Schedule, SectionBounds, TimeEvaluation, Waketime},
+	service::{self, InitSystem},
+	state, tune,
+	tweaks::{self, Tweak},
+	wallpaper,
+};
 use chrono::prelude::*;
-use clap::{Args, Parser, Subcommand};
-use config::AppConfig;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
 use std::{
-	ffi::OsStr,
-	process::{Command, Output},
+	io::{BufRead, Write},
+	path::{Path, PathBuf},
+	process::ExitCode,
+	time::Duration,
 };
+use tracing::{debug, info, warn};
+use tracing_subscriber::EnvFilter;
 use v_utils::io::ExpandedPath;
-pub mod config;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
 	#[command(subcommand)]
 	command: Commands,
-	#[arg(long, default_value = "~/.config/auto_redshift.toml")]
-	config: ExpandedPath,
+	/// `$XDG_CONFIG_HOME/auto_redshift.toml` if not given, falling back to `~/.config/auto_redshift.toml`
+	#[arg(long, env = "AUTO_REDSHIFT_CONFIG")]
+	config: Option<ExpandedPath>,
+	/// Preset for `temperature_range`, if config doesn't set one explicitly
+	#[arg(long, value_enum)]
+	color_profile: Option<ColorProfile>,
+	/// One of the `[profiles.<name>]` from config, laid over the rest of it
+	#[arg(long, env = "AUTO_REDSHIFT_PROFILE")]
+	profile: Option<String>,
+	/// Log as one json object per line, for Loki and the like
+	#[cfg(feature = "json")]
+	#[arg(long)]
+	json_logs: bool,
 }
 #[derive(Subcommand)]
 enum Commands {
 	Start(StartArgs),
+	/// Set gamma for the current time once and exit, for running off a timer instead of as a daemon
+	Apply(ApplyArgs),
+	/// Override the schedule of a running daemon with one of the `[presets]` from config, until cleared
+	Preset(PresetArgs),
+	/// Push the warm end `extreme_extra_kelvin` past `temperature_range` until cleared, or until next morning
+	Extreme(ExtremeArgs),
+	/// Cycle wallpapers as day sections change, like `start --wallpapers`, without ever touching gamma
+	Wallpapers(WallpapersArgs),
+	/// Print what the schedule asks for right now as a line of json, for status bars and widgets
+	Status(StatusArgs),
+	/// Write the whole day's schedule as csv, for plotting
+	Profile(ProfileArgs),
+	/// Print what the schedule does over the day, starting from waketime
+	Preview(PreviewArgs),
+	/// Nudge n_hours and waketime, seeing the preview change with each. The screen is only touched when asked to
+	Tune(TuneArgs),
+	/// Step through warmer and dimmer values live, and write the most extreme comfortable ones into config
+	Calibrate,
+	/// Print the outputs the compositor knows about, by the names `outputs_include` and `outputs_exclude` go by, and what gamma is at now
+	ListOutputs,
+	/// Time successive gamma sets against the real backend, to see how often it can afford to be called
+	BenchBackend(BenchBackendArgs),
+	/// Print the completion script for `shell`, eg `auto_redshift completions fish > ~/.config/fish/completions/auto_redshift.fish`
+	Completions(CompletionsArgs),
+	/// Print a service definition running `start` with this binary and config, eg `auto_redshift generate-service --init systemd -- --wallpapers 7:30`
+	GenerateService(GenerateServiceArgs),
+}
+impl Commands {
+	fn schedule_mut(&mut self) -> Option<&mut ScheduleArgs> {
+		match self {
+			Self::Start(args) => Some(&mut args.schedule),
+			Self::Apply(args) => Some(&mut args.schedule),
+			Self::Wallpapers(args) => Some(&mut args.schedule),
+			Self::Status(args) => Some(&mut args.schedule),
+			Self::Profile(args) => Some(&mut args.schedule),
+			Self::Preview(args) => Some(&mut args.schedule),
+			Self::Tune(args) => Some(&mut args.schedule),
+			Self::Preset(_)
+			| Self::Extreme(_)
+			| Self::Calibrate
+			| Self::ListOutputs
+			| Self::BenchBackend(_)
+			| Self::Completions(_)
+			| Self::GenerateService(_) => None,
+		}
+	}
+}
+
+#[derive(Args, Clone, Debug, Copy)]
+struct TuneArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+	/// Minutes between rows
+	#[arg(long, default_value_t = 60)]
+	step: u32,
+}
+
+#[derive(Args, Clone, Debug)]
+struct GenerateServiceArgs {
+	#[arg(long, visible_alias = "init-system", value_enum)]
+	init: InitSystem,
+	/// Passed on to `start`, like the waketime if config has none
+	#[arg(last = true)]
+	start_args: Vec<String>,
+}
+
+#[derive(Args, Clone, Debug, Copy)]
+struct CompletionsArgs {
+	#[arg(value_enum)]
+	shell: clap_complete::Shell,
+}
+
+#[derive(Args, Clone, Debug, Copy)]
+struct BenchBackendArgs {
+	/// How many sets to time
+	#[arg(long, default_value_t = 20)]
+	count: u32,
+}
+
+#[derive(Args, Clone, Debug, Copy)]
+struct WallpapersArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
 }
 
-#[derive(Args, Clone, Debug, Default, Copy)]
+#[derive(Args, Clone, Debug, Copy)]
+struct StatusArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+	/// Keep printing a line every this many seconds until killed, for a widget to listen on
+	#[arg(long, value_name = "SECONDS")]
+	poll: Option<u64>,
+	/// Say in words why the current values are what they are, instead of printing json
+	#[arg(long)]
+	explain: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+#[group(required = true, multiple = false)]
+struct PresetArgs {
+	name: Option<String>,
+	/// Go back to following the schedule
+	#[arg(long)]
+	clear: bool,
+}
+
+#[derive(Args, Clone, Debug, Copy)]
+struct ExtremeArgs {
+	/// Go back to the normal range
+	#[arg(long)]
+	clear: bool,
+}
+
+#[derive(Args, Clone, Debug)]
+struct ApplyArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+	/// Set even if it's what the last `apply` already set
+	#[arg(long)]
+	force: bool,
+}
+
+#[derive(Args, Clone, Debug, Copy)]
 struct StartArgs {
 	/// Cycle through wallpapers as day phases change
 	#[arg(long)]
 	wallpapers: bool,
+	/// Leave the wallpaper alone until the first section change, instead of setting it on start too
+	#[arg(long, requires = "wallpapers")]
+	no_wallpaper_on_start: bool,
+
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+
+	/// Raise brightness towards the night instead of dimming, for e-ink and reflective displays
+	#[arg(long)]
+	invert_brightness: bool,
 
-	waketime: Waketime,
+	/// Every this many seconds, check that nothing else changed gamma under us, and re-apply if it did
+	#[arg(long)]
+	check_interval: Option<u64>,
+
+	/// After a long gap, like a suspend, fire hooks of every section skipped over, not just of the last one
+	#[arg(long)]
+	catch_up: bool,
+
+	/// Fade over this many seconds whenever the day section changes. Ticks within a section still apply instantly
+	#[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "2")]
+	fade_on_phase_change: Option<f32>,
+
+	/// Move temperature by no more than this many Kelvin each cycle, spreading bigger jumps, like those after a resume, over the next ones
+	#[arg(long, value_name = "KELVIN")]
+	max_temperature_change_per_cycle: Option<f32>,
+
+	/// Local time after which gamma is left as it is until waketime. Takes precedence over `freeze_after` from config
+	#[arg(long)]
+	freeze_after: Option<NaiveTime>,
+
+	/// Fix the RNG seed, so that dithering, and anything else random, is reproducible
+	#[arg(long)]
+	deterministic: bool,
+
+	/// Run on a clock starting at this time, that jumps ahead by however long the loop sleeps, to go through a day in seconds
+	#[arg(long, value_name = "RFC3339", hide = true)]
+	virtual_clock: Option<DateTime<Utc>>,
+
+	/// Re-apply as soon as the machine wakes up from suspend, instead of at the next tick
+	#[cfg(feature = "resume")]
+	#[arg(long)]
+	apply_on_resume: bool,
+
+	/// Publish the current temperature, brightness and section as `org.auto_redshift` on the session bus, with a `Reset` method
+	#[cfg(feature = "dbus")]
+	#[arg(long)]
+	dbus: bool,
+
+	/// Read config again whenever it's saved, keeping the running one if the new one doesn't parse. The backend, curve, calendar and fifo stay as they were on startup
+	#[cfg(feature = "watch-config")]
+	#[arg(long)]
+	watch_config: bool,
 }
 
-#[derive(Clone, Debug, Default, derive_new::new, Copy)]
-struct Waketime {
-	hours: u32,
-	minutes: u32,
+#[derive(Args, Clone, Debug, Copy)]
+struct ScheduleArgs {
+	/// Length of the evening ramp, in hours, which goes up to the night in 20 whole steps. Fallback for when config doesn't specify it for the current month
+	#[arg(long, visible_alias = "n_hours", default_value_t = 4.0, value_parser = positive_hours)]
+	n_hours: f32,
+
+	/// Takes precedence over `waketime` from config, and is required without it
+	waketime: Option<Waketime>,
 }
-impl From<String> for Waketime {
-	fn from(s: String) -> Self {
-		let split: Vec<_> = s.split(':').collect();
-		assert!(split.len() == 2, "ERROR: waketime should be supplied in the format: \"%H:%M\"");
-		let hours: u32 = split[0].parse().unwrap();
-		let minutes: u32 = split[1].parse().unwrap();
-		Waketime { hours, minutes }
+impl ScheduleArgs {
+	fn waketime(&self) -> Waketime {
+		self.waketime.expect("filled in from config before any command runs")
 	}
 }
 
-fn main() {
+/// A ramp of 0 hours or less would have redshift jump to infinity
+fn positive_hours(s: &str) -> Result<f32, String> {
+	match s.parse::<f32>() {
+		Ok(hours) if hours.is_finite() && hours > 0.0 => Ok(hours),
+		Ok(hours) => Err(format!("has to be more than 0 hours, got {hours}")),
+		Err(e) => Err(e.to_string()),
+	}
+}
+
+#[derive(Args, Clone, Debug)]
+struct ProfileArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+	/// Minutes between rows
+	#[arg(long, default_value_t = 30)]
+	step: u32,
+	/// File to write the csv to; stdout if not given
+	#[arg(long)]
+	profile_output: Option<PathBuf>,
+}
+
+#[derive(Args, Clone, Debug)]
+struct PreviewArgs {
+	#[command(flatten)]
+	schedule: ScheduleArgs,
+	/// Minutes between rows
+	#[arg(long, default_value_t = 60)]
+	step: u32,
+	#[arg(long, value_enum, default_value_t)]
+	output_format: OutputFormat,
+	/// Plot temperature and brightness over the day across the terminal, instead of printing a row every `step`
+	#[arg(long, conflicts_with_all = ["step", "output_format"])]
+	graph: bool,
+	/// Day to preview, like `2025-12-21`, for what the seasonal `n_hours` make of it. Today if not given
+	#[arg(long)]
+	date: Option<NaiveDate>,
+}
+
+/// Whatever needs to survive between cycles of the daemon
+#[derive(Debug, Default)]
+struct DaemonState {
+	/// Gamma is held at day values because of a fullscreen window
+	suspended: bool,
+	/// Every display is powered down, with `dpms_aware`
+	displays_off: bool,
+	/// Outputs gamma goes to, as of the last poll with `watch_outputs`
+	targeted_outputs: Option<Vec<String>>,
+	/// `None` until the first cycle
+	section: Option<DaySection>,
+	/// Preset that is currently applied in place of the schedule
+	preset: Option<String>,
+	/// Whether the last cycle was in extreme mode
+	extreme: bool,
+	/// Past `freeze_after`, and not yet at waketime
+	frozen: bool,
+	/// What we last sent to the backend
+	last_applied: Option<DisplaySettings>,
+	/// Running average of the targets, before dithering
+	smoothed: Option<DisplaySettings>,
+	/// When `set_redshift` last ran
+	last_evaluated: Option<DateTime<Utc>>,
+	/// Taken by the first apply, to ease in over
+	soft_start: Option<Duration>,
+	/// Shortest time between backend writes
+	min_apply_interval: Duration,
+	/// When the backend was last written to
+	last_write: Option<DateTime<Utc>>,
+	/// Came in too soon after the last write, to be applied once `min_apply_interval` is up
+	deferred: Option<DisplaySettings>,
+	/// Set on a section change, for the apply of that same cycle to fade over
+	phase_fade: Option<Duration>,
+	/// Schedule is to be applied as is on the next cycle, whatever is applied now
+	reset: bool,
+	/// Last values dithered in `lower_gamma`, before and after, for gamma to be held at the former rather than the latter
+	dithered: Option<(DisplaySettings, DisplaySettings)>,
+	/// Night came in from another section, and its level is yet to be set as is, being possibly cooler than where the evening ended
+	nightfall: bool,
+	/// Wakeups from suspend, if we listen for them
+	#[cfg(feature = "resume")]
+	resumes: Option<std::sync::mpsc::Receiver<()>>,
+	#[cfg(feature = "calendar")]
+	calendar: Option<auto_redshift::calendar::Calendar>,
+	/// From `curve_file`, in place of the schedule
+	curve: Option<Curve>,
+	/// Last wallpaper set from the curve
+	curve_wallpaper: Option<PathBuf>,
+	/// Factor the wallpaper was last dimmed by, with `wallpapers.dim`
+	wallpaper_dim: Option<f32>,
+	/// Last one set, for the next one to fade in from
+	wallpaper_shown: Option<PathBuf>,
+	#[cfg(feature = "watch-config")]
+	config_watcher: Option<auto_redshift::watch::ConfigWatcher>,
+	/// Sleep was cut short for the reload
+	config_changed: bool,
+	#[cfg(feature = "crossfade")]
+	crossfade: Option<auto_redshift::crossfade::Crossfade>,
+	#[cfg(feature = "dbus")]
+	dbus: Option<auto_redshift::dbus::Service>,
+}
+
+fn main() -> ExitCode {
 	let cli = Cli::parse();
-	let config = config::AppConfig::read(cli.config.as_ref()).unwrap();
+	#[cfg(feature = "json")]
+	init_logging(cli.json_logs);
+	#[cfg(not(feature = "json"))]
+	init_logging(false);
+	match run(cli) {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(e) => {
+			// picks the exit code when it's the outermost error; anything else exits with 1
+			let failure = e.downcast_ref::<Error>();
+			// a wrapper is only there for the exit code, the rest of the chain says what actually happened
+			let message: Vec<String> = e
+				.chain()
+				.skip(failure.is_some_and(Error::is_wrapper) as usize)
+				.map(ToString::to_string)
+				.collect();
+			eprintln!("{}", message.join(": "));
+			ExitCode::from(failure.map_or(1, Error::exit_code))
+		}
+	}
+}
+
+fn run(mut cli: Cli) -> anyhow::Result<()> {
+	// has to work before there is any config
+	if let Commands::Completions(args) = cli.command {
+		let mut command = Cli::command();
+		let name = command.get_name().to_owned();
+		clap_complete::generate(args.shell, &mut command, name, &mut std::io::stdout());
+		return Ok(());
+	}
+	let path = match &cli.config {
+		Some(path) => path.as_ref().to_path_buf(),
+		None => auto_redshift::config::default_path(),
+	};
+	let path = path.as_path();
+	// config is only read by the service, and may well not be there yet
+	if let Commands::GenerateService(args) = &cli.command {
+		let binary = std::env::current_exe().context("failed to find out where this binary is")?;
+		let config = std::env::current_dir().map(|dir| dir.join(path)).unwrap_or_else(|_| path.to_owned());
+		print!("{}", service::render(args.init, &binary, &config, &args.start_args));
+		return Ok(());
+	}
+	let mut config = AppConfig::read(path, cli.color_profile, cli.profile.as_deref().filter(|p| !p.is_empty()))?;
+	if let Some(schedule) = cli.command.schedule_mut() {
+		let waketime = schedule.waketime.or_else(|| config.resolve_waketime());
+		schedule.waketime = Some(waketime.ok_or_else(|| Error::Config(anyhow::anyhow!("no waketime, pass one like `7:30` or set `waketime` in config")))?);
+	}
 	match cli.command {
-		Commands::Start(args) => start(config, args),
-	}
-}
-
-fn start(config: AppConfig, args: StartArgs) {
-	let waketime = args.waketime;
-	// dancing with tambourine to get into the 30m cycle
-	// god forgive me
-	let good_minutes_small = (waketime.minutes + 1) % 30; // +1 is offset of the cycle by 1m, to prevent bugs from having undecisive behavior on definition borders
-	let good_minutes_big = good_minutes_small + 30;
-	let m = Utc::now().minute();
-	let mut _wait_to_sync_m = 0;
-	if m <= good_minutes_small && good_minutes_small != 0 {
-		_wait_to_sync_m = good_minutes_small - m;
-	} else if m <= good_minutes_big {
-		_wait_to_sync_m = good_minutes_big - m;
-	} else {
-		_wait_to_sync_m = good_minutes_small + 60 - m;
+		Commands::Start(args) => {
+			config.invert_brightness |= args.invert_brightness;
+			let source = ConfigSource {
+				path,
+				color_profile: cli.color_profile,
+				profile: cli.profile.as_deref().filter(|p| !p.is_empty()),
+			};
+			match args.virtual_clock {
+				Some(at) => start(config, args, &VirtualClock::new(at), &source),
+				None => start(config, args, &SystemClock, &source),
+			}
+		}
+		Commands::Apply(args) => apply_once(&config, args),
+		Commands::Preset(args) => preset(&config, args),
+		Commands::Extreme(args) => state::set_extreme(!args.clear),
+		Commands::Wallpapers(args) => wallpapers_only(&config, args),
+		Commands::Status(args) => status(&config, args),
+		Commands::Profile(args) => profile(&config, args),
+		Commands::Preview(args) => {
+			preview(&config, args);
+			Ok(())
+		}
+		Commands::Tune(args) => tune(&config, args),
+		Commands::Calibrate => calibrate(&config, path),
+		Commands::ListOutputs => list_outputs(&config),
+		Commands::BenchBackend(args) => bench_backend(&config, args),
+		Commands::Completions(_) | Commands::GenerateService(_) => unreachable!("handled before reading config"),
+	}
+}
+
+/// Level is controlled by `RUST_LOG`, `info` by default
+fn init_logging(json: bool) {
+	let builder = tracing_subscriber::fmt()
+		.with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+		.with_writer(std::io::stderr);
+	match json {
+		#[cfg(feature = "json")]
+		true => builder.json().init(),
+		_ => builder.init(),
+	}
+}
+
+/// Where config was read from, for `--watch-config` to read it again
+#[cfg_attr(not(feature = "watch-config"), allow(dead_code))]
+struct ConfigSource<'a> {
+	path: &'a Path,
+	color_profile: Option<ColorProfile>,
+	profile: Option<&'a str>,
+}
+
+#[cfg_attr(not(feature = "watch-config"), allow(unused_variables))]
+fn start(mut config: AppConfig, mut args: StartArgs, clock: &dyn Clock, source: &ConfigSource) -> anyhow::Result<()> {
+	// everything from here on goes by the time of day
+	clock::wait_until_plausible(clock);
+	let until_sync = wait_to_sync(args.schedule.waketime(), clock.now().time());
+	if config.curve_file.is_none() {
+		let transitions = schedule(&config, &args.schedule, clock.now()).transitions();
+		info!(
+			"screen starts warming at {} UTC, night is from {} UTC",
+			transitions.evening_ramp.format("%H:%M"),
+			transitions.night.format("%H:%M")
+		);
+	}
+	let mut state = DaemonState {
+		soft_start: (config.soft_start_s > 0.0).then(|| Duration::from_secs_f32(config.soft_start_s)),
+		min_apply_interval: Duration::from_secs_f32(config.min_apply_interval_s.max(0.0)),
+		displays_off: config.dpms_aware && displays_off(),
+		..Default::default()
+	};
+	let mut backend = backend::from_config(&config).map_err(Error::Env)?;
+	backend.check().map_err(Error::Env)?;
+	backend::wait_ready(Duration::from_secs(config.startup_timeout_s), clock, || backend.probe()).map_err(Error::Backend)?;
+	if config.ambient.enabled && ambient::read_lux(config.ambient.device.as_deref()).is_none() {
+		warn!("no ambient light sensor could be read, following the schedule only");
+	}
+	if config.battery.enabled && battery::read_charge(config.battery.device.as_deref()).is_none() {
+		warn!("no battery could be read, brightness won't follow its charge");
+	}
+	if let Some(path) = &config.curve_file {
+		let curve = Curve::read(path).map_err(Error::Config)?;
+		info!("following the curve in {} instead of the schedule", path.display());
+		if curve.wallpaper_at(NaiveTime::MIN).is_some() {
+			require_binary("swaymsg", "install sway, or drop wallpapers from the curve").map_err(Error::Env)?;
+		}
+		state.curve = Some(curve);
+	} else if args.wallpapers {
+		check_wallpapers(&config, "--wallpapers")?;
+	}
+	attach_calendar(&config, &mut state)?;
+	#[cfg(feature = "resume")]
+	if args.apply_on_resume {
+		state.resumes = Some(auto_redshift::resume::listen().map_err(Error::Env)?);
+	}
+	#[cfg(feature = "dbus")]
+	if args.dbus {
+		state.dbus = Some(auto_redshift::dbus::Service::register().map_err(Error::Env)?);
+	}
+	#[cfg(feature = "watch-config")]
+	if args.watch_config {
+		state.config_watcher = Some(auto_redshift::watch::ConfigWatcher::new(source.path).map_err(Error::Env)?);
+	}
+	let tweaks = match &config.tweak_fifo {
+		Some(path) => Some(tweaks::listen(path).map_err(Error::Env)?),
+		None => None,
+	};
+	// the one source of randomness for everything the daemon does, so that a seed makes all of it reproducible
+	let mut rng = match args.deterministic {
+		true => StdRng::seed_from_u64(0),
+		false => StdRng::from_entropy(),
+	};
+	set_redshift(&config, &args, &mut state, backend.as_mut(), clock, &mut rng);
+	sleep(&config, &args, &mut state, backend.as_mut(), until_sync, clock, &mut rng);
+	loop {
+		// sleep was cut short for it, so the cycle after has to get back in sync
+		let config_changed = std::mem::take(&mut state.config_changed);
+		#[cfg(feature = "watch-config")]
+		if config_changed {
+			reload_config(&mut config, &args, source, &mut state);
+		}
+		for tweak in tweaks.iter().flat_map(|t| t.try_iter()) {
+			info!("tweaked: {tweak:?}");
+			match tweak {
+				// also overrides whatever config has for the month
+				Tweak::NHours(n_hours) => {
+					config.n_hours = None;
+					args.schedule.n_hours = n_hours;
+				}
+				Tweak::Waketime(waketime) => args.schedule.waketime = Some(waketime),
+			}
+		}
+		set_redshift(&config, &args, &mut state, backend.as_mut(), clock, &mut rng);
+		let until_next = match config_changed {
+			true => wait_to_sync(args.schedule.waketime(), clock.now().time()),
+			false => Duration::from_secs(30 * 60),
+		};
+		sleep(&config, &args, &mut state, backend.as_mut(), until_next, clock, &mut rng);
+	}
+}
+
+/// What's only looked at on startup stays as it was
+#[cfg(feature = "watch-config")]
+fn reload_config(config: &mut AppConfig, args: &StartArgs, source: &ConfigSource, state: &mut DaemonState) {
+	match AppConfig::read(source.path, source.color_profile, source.profile) {
+		Ok(mut reloaded) => {
+			reloaded.invert_brightness |= args.invert_brightness;
+			if reloaded.backend != config.backend {
+				warn!("backend changed to {:?}, which only takes effect on restart", reloaded.backend);
+			}
+			state.min_apply_interval = Duration::from_secs_f32(reloaded.min_apply_interval_s.max(0.0));
+			info!("config changed, reloaded it");
+			*config = reloaded;
+		}
+		Err(e) => {
+			// the wrapper only says that it's config, which goes without saying here
+			let e: anyhow::Error = match e {
+				Error::Config(e) => e,
+				e => e.into(),
+			};
+			warn!("keeping the running config: {e:#}");
+		}
+	}
+}
+
+/// Whether `--watch-config` saw config saved, done with whatever bursts of writes the editor does
+#[cfg_attr(not(feature = "watch-config"), allow(unused_variables))]
+fn config_saved(state: &mut DaemonState) -> bool {
+	#[cfg(feature = "watch-config")]
+	if let Some(watcher) = &mut state.config_watcher {
+		return watcher.changed();
+	}
+	false
+}
+
+#[cfg_attr(not(feature = "watch-config"), allow(unused_variables))]
+fn watches_config(state: &DaemonState) -> bool {
+	#[cfg(feature = "watch-config")]
+	return state.config_watcher.is_some();
+	#[cfg(not(feature = "watch-config"))]
+	false
+}
+
+fn check_wallpapers(config: &AppConfig, needed_by: &str) -> anyhow::Result<()> {
+	if !config.wallpapers.is_configured() {
+		return Err(Error::Config(anyhow!("{needed_by} needs a [wallpapers] section in config")).into());
+	}
+	require_binary("swaymsg", &format!("install sway, or drop {needed_by}")).map_err(Error::Env)?;
+	if config.wallpapers.dim.is_some() {
+		require_binary("magick", "install imagemagick, or drop `wallpapers.dim`").map_err(Error::Env)?;
+	}
+	if cfg!(not(feature = "crossfade")) && config.wallpapers.crossfade_minutes > 0.0 {
+		return Err(Error::Env(anyhow!(
+			"wallpapers.crossfade_minutes is set, but this was built without the `crossfade` feature"
+		))
+		.into());
+	}
+	Ok(())
+}
+
+/// Darkened for `redshift` with `wallpapers.dim`, falling back on the original if that fails. Comes with the factor it was dimmed by
+fn wallpaper_for(config: &AppConfig, section: DaySection, redshift: f32) -> (PathBuf, f32) {
+	let path = config.wallpapers.path(section);
+	let factor = config.wallpapers.dim.map_or(1.0, |darkest| wallpaper::dim_factor(redshift, darkest));
+	let path = wallpaper::dimmed(&path, factor).unwrap_or_else(|e| {
+		warn!("showing the wallpaper as is: {e:#}");
+		path
+	});
+	(path, factor)
+}
+
+fn set_wallpaper(config: &AppConfig, section: DaySection, redshift: f32) -> f32 {
+	let (path, factor) = wallpaper_for(config, section, redshift);
+	let _ = cmd(config.wallpapers.command_for(&path));
+	factor
+}
+
+/// Fades into `path` from whatever was shown before with `wallpapers.crossfade_minutes`, and sets it right away otherwise
+#[cfg_attr(not(feature = "crossfade"), allow(unused_variables))]
+fn change_wallpaper(config: &AppConfig, state: &mut DaemonState, path: PathBuf) {
+	let previous = state.wallpaper_shown.replace(path.clone());
+	#[cfg(feature = "crossfade")]
+	if let Some(from) = previous.filter(|from| config.wallpapers.crossfade_minutes > 0.0 && *from != path) {
+		let window = Duration::from_secs_f32(config.wallpapers.crossfade_minutes * 60.0);
+		state.crossfade = Some(auto_redshift::crossfade::Crossfade::new(from, path, window));
+		step_crossfade(config, state);
+		return;
+	}
+	let _ = cmd(config.wallpapers.command_for(&path));
+}
+
+/// Shows the next image of the fade going on, if it's time for one
+#[cfg_attr(not(feature = "crossfade"), allow(unused_variables))]
+fn step_crossfade(config: &AppConfig, state: &mut DaemonState) {
+	#[cfg(feature = "crossfade")]
+	if let Some(fade) = &mut state.crossfade {
+		let path = match fade.step() {
+			Some(Ok(path)) => path,
+			Some(Err(e)) => {
+				warn!("failed to blend wallpapers, switching straight to the new one: {e:#}");
+				let path = fade.to().to_owned();
+				state.crossfade = None;
+				path
+			}
+			None => return,
+		};
+		let _ = cmd(config.wallpapers.command_for(&path));
+		if state.crossfade.as_ref().is_some_and(|f| f.is_done()) {
+			state.crossfade = None;
+		}
+	}
+}
+
+/// How often the fade going on has a new image to show
+#[cfg_attr(not(feature = "crossfade"), allow(unused_variables))]
+fn crossfade_tick(state: &DaemonState) -> Option<Duration> {
+	#[cfg(feature = "crossfade")]
+	return state.crossfade.as_ref().map(|f| f.tick().max(Duration::from_secs(1)));
+	#[cfg(not(feature = "crossfade"))]
+	None
+}
+
+/// No backend is ever made, so this runs even where none would work
+fn wallpapers_only(config: &AppConfig, args: WallpapersArgs) -> anyhow::Result<()> {
+	check_wallpapers(config, "`wallpapers`")?;
+	let mut current = None;
+	loop {
+		let now = Utc::now();
+		let TimeEvaluation {
+			day_section: section,
+			redshift,
+			..
+		} = evaluate_time(now.time(), &schedule(config, &args.schedule, now));
+		let factor = config.wallpapers.dim.map_or(1.0, |darkest| wallpaper::dim_factor(redshift, darkest));
+		if current != Some((section, factor)) {
+			info!("{section}, setting its wallpaper");
+			set_wallpaper(config, section, redshift);
+			current = Some((section, factor));
+		}
+		std::thread::sleep(Duration::from_secs(60));
+	}
+}
+
+/// `settings_for`, with the warm end stretched in extreme mode
+fn scheduled_settings(section: DaySection, redshift: f32, config: &AppConfig, extreme: bool) -> DisplaySettings {
+	match extreme {
+		true => calculate_display_settings(redshift, config)
+			.warmer_by(config.extreme_extra_kelvin, config)
+			.overridden_by(config.overrides.get(section)),
+		false => settings_for(section, redshift, config),
+	}
+}
+
+/// Skips the backend altogether if nothing changed since the last run, so that a timer firing doesn't flash the screen
+fn apply_once(config: &AppConfig, args: ApplyArgs) -> anyhow::Result<()> {
+	let now = Utc::now();
+	if !clock::plausible(now) {
+		return Err(Error::Env(anyhow!("the clock reads {now}, which can't be right; not applying anything until it's set")).into());
+	}
+	let mut state = DaemonState::default();
+	attach_calendar(config, &mut state)?;
+	let target = target_at(config, &args.schedule, now, &mut state);
+	if !args.force && state::last_applied().is_some_and(|last| !drifted(last, target)) {
+		info!("{target} is already set, skipping");
+		return Ok(());
+	}
+	let mut backend = backend::from_config(config).map_err(Error::Env)?;
+	backend.check().map_err(Error::Env)?;
+	target.validate().context("refusing to apply")?;
+	backend.set(target.temperature, target.brightness).map_err(Error::Backend)?;
+	info!("applied {target}");
+	state::set_last_applied(target)
+}
+
+/// What a one-off apply would set at `now`: the active preset, or else the schedule, held at day values through blackouts and meetings
+fn target_at(config: &AppConfig, args: &ScheduleArgs, now: DateTime<Utc>, state: &mut DaemonState) -> DisplaySettings {
+	match requested_preset(config) {
+		Some(name) => config.presets[&name],
+		None => {
+			let TimeEvaluation { day_section, redshift, .. } = evaluate_time(now.time(), &schedule(config, args, now));
+			match config.blackout.iter().any(|w| w.contains(now.with_timezone(&Local).time())) || in_meeting(state, now) {
+				true => settings_for(DaySection::Day, 0.0, config),
+				false => scheduled_settings(day_section, redshift, config, state::extreme()),
+			}
+		}
+	}
+}
+
+fn status(config: &AppConfig, args: StatusArgs) -> anyhow::Result<()> {
+	let mut state = DaemonState::default();
+	attach_calendar(config, &mut state)?;
+	// locked for the whole run, so that every line goes out whole, and flushed right after
+	let mut stdout = std::io::stdout().lock();
+	loop {
+		let now = Utc::now();
+		let TimeEvaluation { day_section, redshift, .. } = evaluate_time(now.time(), &schedule(config, &args.schedule, now));
+		let target = target_at(config, &args.schedule, now, &mut state);
+		let line = match args.explain {
+			true => explain(config, &args.schedule, now, target, &mut state),
+			false => serde_json::json!({
+				"section": day_section,
+				"redshift": redshift,
+				"temperature": target.temperature,
+				"brightness": target.brightness,
+				"preset": requested_preset(config),
+				"extreme": state::extreme(),
+				"last_applied": state::last_applied(),
+			})
+			.to_string(),
+		};
+		match writeln!(stdout, "{line}").and_then(|()| stdout.flush()) {
+			// whoever was listening is gone
+			Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => return Ok(()),
+			written => written?,
+		}
+		match args.poll {
+			Some(seconds) => std::thread::sleep(Duration::from_secs(seconds.max(1))),
+			None => return Ok(()),
+		}
+	}
+}
+
+/// The schedule's reasoning, then whatever took over from it
+fn explain(config: &AppConfig, args: &ScheduleArgs, now: DateTime<Utc>, target: DisplaySettings, state: &mut DaemonState) -> String {
+	let because = auto_redshift::schedule::explain(now.time(), &schedule(config, args, now));
+	let values = format!("{}K at {:.2} brightness", target.temperature, target.brightness);
+	match requested_preset(config) {
+		Some(name) => format!("{because}, but preset `{name}` is on, so it's {values}."),
+		None if config.blackout.iter().any(|w| w.contains(now.with_timezone(&Local).time())) => {
+			format!("{because}, but it's a blackout, so it's {values}.")
+		}
+		None if in_meeting(state, now) => format!("{because}, but the calendar has a meeting on, so it's {values}."),
+		None if state::extreme() => format!("{because}, in extreme mode → {values}."),
+		None => format!("{because} → {values}."),
+	}
+}
+
+fn preset(config: &AppConfig, args: PresetArgs) -> anyhow::Result<()> {
+	match args.name {
+		Some(name) => {
+			let name = name.to_lowercase();
+			if !config.presets.contains_key(&name) {
+				return Err(Error::Config(anyhow!("no preset `{name}` in config; have: {:?}", config.presets.keys().collect::<Vec<_>>())).into());
+			}
+			state::set_active_preset(&name)
+		}
+		None => state::clear_active_preset(),
+	}
+}
+
+fn profile(config: &AppConfig, args: ProfileArgs) -> anyhow::Result<()> {
+	let schedule = schedule(config, &args.schedule, Utc::now());
+	let csv = auto_redshift::profile::to_csv(&schedule, chrono::Duration::minutes(args.step.max(1) as i64), config);
+	match args.profile_output {
+		Some(path) => std::fs::write(&path, csv).with_context(|| format!("failed to write {}", path.display())),
+		None => {
+			print!("{csv}");
+			Ok(())
+		}
 	}
-	set_redshift(&config, &waketime, args.wallpapers);
-	std::thread::sleep(std::time::Duration::from_secs(_wait_to_sync_m as u64 * 60));
+}
+
+fn preview(config: &AppConfig, args: PreviewArgs) {
+	let day = args.date.map_or_else(Utc::now, |date| date.and_time(NaiveTime::MIN).and_utc());
+	match args.graph {
+		true => print!("{}", render_graph(config, &args.schedule, day)),
+		false => print!("{}", render_preview(config, &args.schedule, args.step, args.output_format, day)),
+	}
+}
+
+/// `$COLUMNS` if exported, whatever `tput` makes of the terminal otherwise, and 80 if neither knows
+fn terminal_width() -> usize {
+	let from_tput = || {
+		let output = std::process::Command::new("tput")
+			.arg("cols")
+			.stderr(std::process::Stdio::inherit())
+			.output()
+			.ok()?;
+		String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+	};
+	std::env::var("COLUMNS").ok().and_then(|c| c.parse().ok()).or_else(from_tput).unwrap_or(80)
+}
+
+/// One column per however much of the day fits across the terminal
+fn render_graph(config: &AppConfig, args: &ScheduleArgs, day: DateTime<Utc>) -> String {
+	const HEIGHT: usize = 8;
+	// room for the labels on the left
+	let columns = terminal_width().saturating_sub(8).max(24);
+	let schedule = schedule(config, args, day);
+	let waketime = args.waketime();
+	let start = NaiveTime::from_hms_opt(waketime.hours, waketime.minutes, waketime.seconds).expect("waketime is a valid time of day");
+	let step = chrono::Duration::seconds((24 * 3600 / columns as i64).max(60));
+	let rows = auto_redshift::profile::rows(&schedule, start, step, config);
+	auto_redshift::profile::graph(&rows, HEIGHT)
+}
+
+/// Schedule in effect on `day`, from waketime on
+fn render_preview(config: &AppConfig, args: &ScheduleArgs, step: u32, format: OutputFormat, day: DateTime<Utc>) -> String {
+	let schedule = schedule(config, args, day);
+	let waketime = args.waketime();
+	let start = NaiveTime::from_hms_opt(waketime.hours, waketime.minutes, waketime.seconds).expect("waketime is a valid time of day");
+	let rows = auto_redshift::profile::rows(&schedule, start, chrono::Duration::minutes(step.max(1) as i64), config);
+	auto_redshift::profile::render(&rows, format)
+}
+
+/// Takes lines of keys from stdin, so several can go at once, like `+++`
+fn tune(config: &AppConfig, args: TuneArgs) -> anyhow::Result<()> {
+	let mut params = tune::Params {
+		n_hours: config
+			.n_hours
+			.as_ref()
+			.and_then(|h| h.for_month(Utc::now().month()))
+			.unwrap_or(args.schedule.n_hours),
+		waketime: args.schedule.waketime(),
+	};
+	let mut config = config.clone();
+	// whatever config has for the month would hide the nudges
+	config.n_hours = None;
+	let mut schedule_args = args.schedule;
+	let mut backend = None;
+	let (stdin, stdout) = (std::io::stdin(), std::io::stdout());
+	let (mut input, mut output) = (stdin.lock(), stdout.lock());
 	loop {
-		set_redshift(&config, &waketime, args.wallpapers);
-		std::thread::sleep(std::time::Duration::from_secs(30 * 60));
+		(schedule_args.n_hours, schedule_args.waketime) = (params.n_hours, Some(params.waketime));
+		write!(
+			output,
+			"{}",
+			render_preview(&config, &schedule_args, args.step, OutputFormat::default(), Utc::now())
+		)?;
+		write!(output, "n_hours {}, waketime {}. {}: ", params.n_hours, params.waketime, tune::HELP)?;
+		output.flush()?;
+		let mut line = String::new();
+		if input.read_line(&mut line)? == 0 {
+			break;
+		}
+		let mut quit = false;
+		for key in line.trim().chars() {
+			match tune::handle(key, &mut params) {
+				tune::Outcome::Changed => {}
+				tune::Outcome::Apply => {
+					if backend.is_none() {
+						let created = backend::from_config(&config).map_err(Error::Env)?;
+						created.check().map_err(Error::Env)?;
+						backend = Some(created);
+					}
+					let backend = backend.as_mut().expect("just created");
+					let mut state = DaemonState::default();
+					let target = target_at(&config, &schedule_args, Utc::now(), &mut state);
+					backend.set(target.temperature, target.brightness).map_err(Error::Backend)?;
+					writeln!(output, "applied {target}")?;
+				}
+				tune::Outcome::Quit => quit = true,
+				tune::Outcome::Unknown => writeln!(output, "unknown key {key:?}")?,
+			}
+		}
+		if quit {
+			break;
+		}
+	}
+	writeln!(output, "\nto keep these: auto_redshift start --n-hours {} {}", params.n_hours, params.waketime)?;
+	Ok(())
+}
+
+fn list_outputs(config: &AppConfig) -> anyhow::Result<()> {
+	let outputs = auto_redshift::outputs::query().map_err(Error::Env)?;
+	let filter = OutputFilter::new(&config.outputs_include, &config.outputs_exclude).map_err(Error::Config)?;
+	print!("{}", auto_redshift::outputs::render(&outputs, &filter));
+	let current = backend::from_config(config).and_then(|mut backend| {
+		backend.check()?;
+		// finds out whether brightness can be read at all
+		backend.probe()?;
+		read_back(backend.as_mut(), 1.0)
+	});
+	match current {
+		Ok(current) => println!("\ngamma is at {}K, {:.2} brightness", current.temperature, current.brightness),
+		Err(e) => println!("\ngamma can't be read back: {e:#}"),
 	}
+	Ok(())
+}
+
+/// Sets what's already there, so that the screen doesn't change while measuring
+fn bench_backend(config: &AppConfig, args: BenchBackendArgs) -> anyhow::Result<()> {
+	let mut backend = backend::from_config(config).map_err(Error::Env)?;
+	backend.check().map_err(Error::Env)?;
+	backend.probe().map_err(Error::Backend)?;
+	let day = DisplaySettings::new(config.temperature_bounds().1, config.brightness_range.1);
+	let current = match read_back(backend.as_mut(), day.brightness) {
+		Ok(current) => current,
+		Err(_) => {
+			warn!("backend can't tell what it has set, measuring with day values of {day}");
+			day
+		}
+	};
+	let latency = backend::measure(backend.as_mut(), current.temperature, current.brightness, args.count).map_err(Error::Backend)?;
+	println!("{latency}");
+	Ok(())
 }
 
-fn set_redshift(config: &AppConfig, waketime: &Waketime, wallpapers: bool) {
-	let nm = Utc::now().hour() * 60 + Utc::now().minute();
-	let wt = waketime.hours * 60 + waketime.minutes;
+fn calibrate(config: &AppConfig, path: &Path) -> anyhow::Result<()> {
+	let mut backend = backend::from_config(config).map_err(Error::Env)?;
+	backend.check().map_err(Error::Env)?;
+	backend.probe().map_err(Error::Backend)?;
+	let before = read_back(backend.as_mut(), 1.0)?;
+	let (stdin, stdout) = (std::io::stdin(), std::io::stdout());
+	let (mut input, mut output) = (stdin.lock(), stdout.lock());
 
-	// shift everything wt minutes back
-	// in python would be `(nm - wt) % 24`, but rust doesn't want to exhibit desired behaviour with % on negative numbers
-	let mut now_shifted = nm as i32 - wt as i32;
-	if now_shifted < 0 {
-		now_shifted += 24 * 60;
+	let day = DisplaySettings::new(config.temperature_bounds().1, config.brightness_range.1);
+	let temperatures = calibrate::steps_down(day.temperature, 1900.0, 400.0);
+	let temperature = calibrate::pick(&temperatures, "K", |t| backend.set(t, day.brightness), &mut input, &mut output)?;
+	// nothing to see in stepping through values that won't be set
+	let brightnesses = match backend.capabilities().brightness {
+		true => calibrate::steps_down(day.brightness, 0.2, 0.1),
+		false => Vec::new(),
+	};
+	let brightness = calibrate::pick(
+		&brightnesses,
+		"",
+		|b| backend.set(temperature.unwrap_or(day.temperature), b),
+		&mut input,
+		&mut output,
+	)?;
+	backend.set(before.temperature, before.brightness).map_err(Error::Backend)?;
+
+	let untouched = match brightnesses.is_empty() {
+		true => config.brightness_range.0,
+		false => day.brightness,
+	};
+	let (temperature, brightness) = (temperature.unwrap_or(day.temperature), brightness.unwrap_or(untouched));
+	calibrate::write_ranges(path, temperature as usize, brightness)?;
+	println!("wrote warmest {temperature}K and dimmest {brightness} to {}", path.display());
+	Ok(())
+}
+
+/// Until the next tick of the 30m cycle, which runs 1m after each half hour from waketime, so that ticks never land right on a section border.
+/// Zero if `now` is within a minute past one. Always under 30m, whatever the fields of `waketime` hold.
+fn wait_to_sync(waketime: Waketime, now: NaiveTime) -> Duration {
+	const CYCLE_S: i64 = 30 * 60;
+	let tick = waketime.as_seconds() as i64 + 60;
+	// a leap second still counts from midnight as the last second of the day
+	let since_tick = (now.num_seconds_from_midnight() as i64 - tick).rem_euclid(CYCLE_S);
+	let wait_s = match since_tick < 60 {
+		true => 0,
+		false => CYCLE_S - since_tick,
+	};
+	Duration::from_secs(wait_s as u64)
+}
+
+#[cfg_attr(not(feature = "calendar"), allow(unused_variables))]
+fn attach_calendar(config: &AppConfig, state: &mut DaemonState) -> anyhow::Result<()> {
+	let Some(source) = &config.calendar.source else {
+		return Ok(());
+	};
+	#[cfg(feature = "calendar")]
+	{
+		let refresh = Duration::from_secs(config.calendar.refresh_minutes * 60);
+		state.calendar = Some(auto_redshift::calendar::Calendar::new(source.clone(), refresh));
+		Ok(())
 	}
+	#[cfg(not(feature = "calendar"))]
+	Err(Error::Env(anyhow!(
+		"calendar.source is set to {source:?}, but this was built without the `calendar` feature"
+	))
+	.into())
+}
 
-	dbg!(&now_shifted);
-	// I guess I could be taking the day section borders as args
-	let day_section: String = match now_shifted {
-		t if (t > 20 * 60) || (t <= 150) => "morning".to_owned(),
-		t if t <= 150 + 8 * 60 => "day".to_owned(),
-		t if t <= 16 * 60 => "evening".to_owned(),
-		_ => "night".to_owned(),
+/// Whether the calendar has a busy event going on
+#[cfg_attr(not(feature = "calendar"), allow(unused_variables))]
+fn in_meeting(state: &mut DaemonState, now: DateTime<Utc>) -> bool {
+	#[cfg(feature = "calendar")]
+	if let Some(calendar) = &mut state.calendar {
+		return calendar.busy(now);
+	}
+	false
+}
+
+/// Schedule in effect at `now`
+fn schedule(config: &AppConfig, args: &ScheduleArgs, now: DateTime<Utc>) -> Schedule {
+	let n_hours = config.n_hours.as_ref().and_then(|h| h.for_month(now.month())).unwrap_or(args.n_hours);
+	let levels = RedshiftLevels {
+		evening_end: config.evening_end_redshift,
+		night: config.night_redshift,
+		day: config.day_redshift,
 	};
+	Schedule::new(
+		args.waketime(),
+		n_hours,
+		SectionBounds::default(),
+		config.grace_minutes,
+		config.morning_n_hours,
+		levels,
+	)
+	.with_wake_periods(config.wake_periods.clone())
+	.with_offset(config.schedule_offset)
+}
 
-	// redshift is a number from 0 to 20
-	let redshift: u32;
-	// wallpapers are in ~/Wallpapers
-	let wallpaper: &str;
-	let brightness_step = (config.brightness_range.1 - config.brightness_range.0) / 20.0;
-	let temperature_step = (config.temperature_range.1 - config.temperature_range.0) as f32 / 20.0;
+/// Active preset, if it's one config knows about
+fn requested_preset(config: &AppConfig) -> Option<String> {
+	state::active_preset().filter(|name| config.presets.contains_key(name))
+}
 
-	match day_section.as_str() {
-		"morning" => {
-			redshift = 0;
-			wallpaper = &config.wallpapers.morning;
+/// Sleeps through `duration`, polling for whatever there is to watch for between cycles (fullscreen windows, presets, gamma drift, the waketime grace ramp, resumes from suspend)
+fn sleep(
+	config: &AppConfig,
+	args: &StartArgs,
+	state: &mut DaemonState,
+	backend: &mut dyn GammaBackend,
+	duration: Duration,
+	clock: &dyn Clock,
+	rng: &mut dyn RngCore,
+) {
+	const GRACE_TICK: Duration = Duration::from_secs(60);
+	let watch_events = config.fullscreen.enabled
+		|| !config.presets.is_empty()
+		|| config.dpms_aware
+		|| config.watch_outputs
+		|| serves_dbus(state)
+		|| watches_config(state);
+	let eases_out = config.grace_minutes > 0.0 || config.morning_n_hours > 0.0;
+	let check_interval = args.check_interval.map(|s| Duration::from_secs(s.max(1)));
+	let intervals = [
+		watch_events.then(|| Duration::from_secs(config.poll_interval_s.max(1))),
+		check_interval,
+		eases_out.then_some(GRACE_TICK),
+		(!state.min_apply_interval.is_zero()).then_some(state.min_apply_interval),
+		crossfade_tick(state),
+	];
+	let poll = intervals.into_iter().flatten().min().unwrap_or(duration);
+	let since = |t: DateTime<Utc>| (clock.now() - t).to_std().unwrap_or_default();
+	let deadline = clock.now() + chrono::Duration::from_std(duration).expect("fits in chrono");
+	let mut last_check = clock.now();
+	let mut last_grace_tick = clock.now();
+	let mut was_in_grace = false;
+	while let Some(left) = (deadline - clock.now()).to_std().ok().filter(|d| !d.is_zero()) {
+		let mut resumed = nap(state, left.min(poll), clock);
+		step_crossfade(config, state);
+		if config_saved(state) {
+			state.config_changed = true;
+			return;
 		}
-		"day" => {
-			redshift = 0;
-			wallpaper = &config.wallpapers.day;
+		if config.dpms_aware {
+			let off = displays_off();
+			if off != state.displays_off {
+				info!(
+					"displays turned {}",
+					match off {
+						true => "off",
+						false => "on",
+					}
+				);
+				state.displays_off = off;
+				// a display coming back on is like a resume, whatever was set may be gone
+				resumed |= !off;
+			}
 		}
-		"evening" => {
-			if now_shifted > 12 * 60 {
-				redshift = ((now_shifted as f32 / 60.0 - 12.0 + 0.5) * 5.0) as u32;
-			} else {
-				redshift = 0;
+		if config.watch_outputs && outputs_changed(config, state, backend) {
+			// new outputs come up at whatever the compositor defaults to, so what we had goes out again
+			if let Some(last) = state.last_applied {
+				if let Err(e) = apply(backend, state, clock, last) {
+					warn!("failed to set gamma on the new outputs: {e:#}");
+				}
 			}
-			wallpaper = &config.wallpapers.evening;
 		}
-		"night" => {
-			redshift = 20;
-			wallpaper = &config.wallpapers.night;
+		if resumed {
+			// whatever we set is likely gone, so daytime values have to be sent again too
+			state.last_applied = None;
+		}
+		let fullscreen_changed = config.fullscreen.enabled && fullscreen::fullscreen_present(&config.fullscreen) != state.suspended;
+		// one more tick right as the grace ends, so that it lands exactly on the day values
+		let in_grace = eases_out && {
+			let now = clock.now();
+			schedule(config, &args.schedule, now).in_grace(now.time())
+		};
+		let grace_tick = (in_grace && since(last_grace_tick) >= GRACE_TICK) || (was_in_grace && !in_grace);
+		was_in_grace = in_grace;
+		if grace_tick {
+			last_grace_tick = clock.now();
+		}
+		if take_reset(state) {
+			info!("reset over dbus");
+			state.reset = true;
+		}
+		if fullscreen_changed || requested_preset(config) != state.preset || grace_tick || resumed || state.reset {
+			set_redshift(config, args, state, backend, clock, rng);
+		}
+		if let Some(settings) = state.deferred.take_if(|_| !state.displays_off) {
+			if let Err(e) = apply(backend, state, clock, settings) {
+				warn!("failed to apply deferred {settings}: {e:#}");
+			}
+		}
+		if !state.displays_off && check_interval.is_some_and(|check| since(last_check) >= check) {
+			last_check = clock.now();
+			watchdog(backend, state, clock);
 		}
-		_ => unreachable!(),
 	}
+}
+
+#[cfg_attr(not(feature = "dbus"), allow(unused_variables))]
+fn serves_dbus(state: &DaemonState) -> bool {
+	#[cfg(feature = "dbus")]
+	return state.dbus.is_some();
+	#[cfg(not(feature = "dbus"))]
+	false
+}
+
+/// Whether `Reset` was called over dbus since the last time
+#[cfg_attr(not(feature = "dbus"), allow(unused_variables))]
+fn take_reset(state: &DaemonState) -> bool {
+	#[cfg(feature = "dbus")]
+	if let Some(dbus) = &state.dbus {
+		return dbus.take_reset();
+	}
+	false
+}
 
-	if redshift != 0 {
-		let temperature: f32 = config.temperature_range.1 as f32 - redshift as f32 * temperature_step;
-		let brightness: f32 = config.brightness_range.1 - redshift as f32 * brightness_step;
+/// Sends whatever changed out over dbus, if we're on it
+#[cfg_attr(not(feature = "dbus"), allow(unused_variables))]
+fn publish(state: &DaemonState, settings: Option<DisplaySettings>, section: Option<DaySection>) {
+	#[cfg(feature = "dbus")]
+	if let Some(dbus) = &state.dbus {
+		let published = settings
+			.map_or(Ok(()), |s| dbus.publish_settings(s))
+			.and_then(|()| section.map_or(Ok(()), |s| dbus.publish_section(s)));
+		if let Err(e) = published {
+			warn!("failed to publish state over dbus: {e:#}");
+		}
+	}
+}
 
-		let extra_characters: &[_] = &['(', ')', ','];
-		let current_temperature_output =
-			cmd("gdbus call -e -d net.zoidplex.wlr_gamma_service -o /net/zoidplex/wlr_gamma_service -m net.zoidplex.wlr_gamma_service.temperature.get");
-		let current_temperature = String::from_utf8_lossy(&current_temperature_output.stdout)
-			.trim()
-			.to_string()
-			.trim_matches(extra_characters)
-			.parse()
-			.unwrap();
-		let current_brightness_output =
-			cmd("gdbus call -e -d net.zoidplex.wlr_gamma_service -o /net/zoidplex/wlr_gamma_service -m net.zoidplex.wlr_gamma_service.brightness.get");
-		let current_brightness = String::from_utf8_lossy(&current_brightness_output.stdout)
-			.trim()
-			.to_string()
-			.trim_matches(extra_characters)
-			.parse()
-			.unwrap();
+/// Goes by the backend if it can tell, and by what sway reports otherwise, which is only compared from the second call on
+fn outputs_changed(config: &AppConfig, state: &mut DaemonState, backend: &mut dyn GammaBackend) -> bool {
+	if let Some(changed) = backend.outputs_changed() {
+		if changed {
+			info!("outputs changed, setting gamma on them again");
+		}
+		return changed;
+	}
+	let filter = OutputFilter::new(&config.outputs_include, &config.outputs_exclude).unwrap_or_default();
+	let targeted = match auto_redshift::outputs::query() {
+		Ok(outputs) => auto_redshift::outputs::targeted(&outputs, &filter),
+		Err(e) => {
+			debug!("couldn't list outputs: {e:#}");
+			return false;
+		}
+	};
+	let Some(before) = state.targeted_outputs.replace(targeted.clone()) else {
+		return false;
+	};
+	let change = auto_redshift::outputs::Change::between(&before, &targeted);
+	if !change.is_empty() {
+		info!(added = ?change.added, removed = ?change.removed, "outputs changed, setting gamma on them again");
+	}
+	!change.is_empty()
+}
 
-		if temperature < current_temperature && brightness < current_brightness {
-			let _ = cmd(format!("gdbus call -e -d net.zoidplex.wlr_gamma_service -o /net/zoidplex/wlr_gamma_service -m net.zoidplex.wlr_gamma_service.temperature.set {} && gdbus call -e -d net.zoidplex.wlr_gamma_service -o /net/zoidplex/wlr_gamma_service -m net.zoidplex.wlr_gamma_service.brightness.set {}", temperature, brightness));
+/// If sway can't be asked, the displays are taken to be on
+fn displays_off() -> bool {
+	match auto_redshift::outputs::query() {
+		Ok(outputs) => auto_redshift::outputs::all_off(&outputs),
+		Err(e) => {
+			debug!("couldn't tell whether the displays are on: {e:#}");
+			false
 		}
 	}
-	if wallpapers {
-		let wallpaper_path = config.wallpapers.root.join(wallpaper);
-		let _ = cmd(format!("swaymsg output '*' bg {} fill", wallpaper_path.to_str().unwrap()));
+}
+
+/// Sleeps, but wakes up early on a resume from suspend, if we listen for those. Returns whether it did.
+#[cfg_attr(not(feature = "resume"), allow(unused_variables))]
+fn nap(state: &mut DaemonState, duration: Duration, clock: &dyn Clock) -> bool {
+	#[cfg(feature = "resume")]
+	if let Some(resumes) = &state.resumes {
+		match resumes.recv_timeout(duration) {
+			Ok(()) => return true,
+			Err(std::sync::mpsc::RecvTimeoutError::Timeout) => return false,
+			Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => state.resumes = None,
+		}
 	}
+	clock.sleep(duration);
+	false
 }
 
-fn cmd<S>(command: S) -> Output
-where
-	S: AsRef<OsStr>,
-{
-	let output = Command::new("sh").arg("-c").arg(command).output().unwrap();
-	output
+/// What the backend has set. Brightness it can't set is taken to be `fallback`, so that only temperature tells anything
+fn read_back(backend: &mut dyn GammaBackend, fallback: f32) -> anyhow::Result<DisplaySettings> {
+	let temperature = backend.get_temperature()?;
+	let brightness = match backend.capabilities().brightness {
+		true => backend.get_brightness()?,
+		false => fallback,
+	};
+	Ok(DisplaySettings::new(temperature, brightness))
+}
+
+/// Re-applies whatever we last set, if something else has since changed it. Doesn't recompute anything.
+fn watchdog(backend: &mut dyn GammaBackend, state: &mut DaemonState, clock: &dyn Clock) {
+	let Some(expected) = state.last_applied else {
+		return;
+	};
+	let current = match read_back(backend, expected.brightness) {
+		Ok(current) => current,
+		Err(e) => {
+			warn!("watchdog failed to read current gamma: {e:#}");
+			return;
+		}
+	};
+	if drifted(current, expected) {
+		info!("gamma drifted to {current}, re-applying {expected}");
+		if let Err(e) = apply(backend, state, clock, expected) {
+			warn!("watchdog failed to re-apply gamma: {e:#}");
+		}
+	}
+}
+
+fn drifted(current: DisplaySettings, expected: DisplaySettings) -> bool {
+	const TEMPERATURE_EPSILON: f32 = 1.0;
+	const BRIGHTNESS_EPSILON: f32 = 0.01;
+	(current.temperature - expected.temperature).abs() > TEMPERATURE_EPSILON || (current.brightness - expected.brightness).abs() > BRIGHTNESS_EPSILON
+}
+
+/// All writes to the backend go through here, so that the watchdog knows what to expect, and so that they can be rate limited
+fn apply(backend: &mut dyn GammaBackend, state: &mut DaemonState, clock: &dyn Clock, settings: DisplaySettings) -> anyhow::Result<()> {
+	settings.validate().context("refusing to apply")?;
+	if state
+		.last_write
+		.is_some_and(|t| (clock.now() - t).to_std().unwrap_or_default() < state.min_apply_interval)
+	{
+		debug!("deferred {settings}: too soon after the last write");
+		state.deferred = Some(settings);
+		return Ok(());
+	}
+	state.deferred = None;
+	state.last_write = Some(clock.now());
+	if let Some(duration) = state.soft_start.take().or_else(|| state.phase_fade.take()) {
+		ease_in(backend, settings, duration, clock)?;
+	}
+	backend.set(settings.temperature, settings.brightness)?;
+	state.last_applied = Some(settings);
+	publish(state, Some(settings), None);
+	Ok(())
+}
+
+/// Steps from whatever the backend currently has up to `target`, stopping one step short of it
+fn ease_in(backend: &mut dyn GammaBackend, target: DisplaySettings, duration: Duration, clock: &dyn Clock) -> anyhow::Result<()> {
+	const STEP: Duration = Duration::from_millis(250);
+	let Ok(from) = read_back(backend, target.brightness) else {
+		return Ok(());
+	};
+	let steps = (duration.as_secs_f32() / STEP.as_secs_f32()).ceil() as u32;
+	for i in 1..steps {
+		let step = from.lerp(target, i as f32 / steps as f32);
+		backend.set(step.temperature, step.brightness)?;
+		clock.sleep(STEP);
+	}
+	Ok(())
+}
+
+fn set_redshift(config: &AppConfig, args: &StartArgs, state: &mut DaemonState, backend: &mut dyn GammaBackend, clock: &dyn Clock, rng: &mut dyn RngCore) {
+	if state.displays_off {
+		debug!("skipped: displays are off");
+		return;
+	}
+	if let Some(curve) = state.curve.clone() {
+		return follow_curve(config, state, backend, &curve, clock);
+	}
+	let now = clock.now();
+	let local = now.with_timezone(&Local).time();
+	let last_evaluated = state.last_evaluated.replace(now);
+	// a fade left over from a cycle that didn't apply anything would land on some ordinary tick
+	state.phase_fade = None;
+	let TimeEvaluation {
+		now_shifted,
+		day_section,
+		redshift,
+	} = evaluate_time(now.time(), &schedule(config, &args.schedule, now));
+	if let Some(at) = args.freeze_after.or(config.freeze_after) {
+		let w = args.schedule.waketime();
+		let waketime = NaiveTime::from_hms_opt(w.hours, w.minutes, w.seconds).expect("waketime is a valid time of day");
+		match last_evaluated {
+			// with nothing to have crossed from, it's whichever of the two came up last
+			Some(last) if now - last < chrono::Duration::days(1) => {
+				if !state.frozen && crossed(at, last.with_timezone(&Local).time(), local) {
+					info!("past {at}, holding gamma until waketime");
+					state.frozen = true;
+				} else if state.frozen && crossed(waketime, last.time(), now.time()) {
+					info!("waketime, following the schedule again");
+					state.frozen = false;
+				}
+			}
+			_ => {
+				let since = |time: NaiveTime, now: NaiveTime| (now - time).num_seconds().rem_euclid(24 * 3600);
+				state.frozen = since(at, local) < since(waketime, now.time());
+				if state.frozen {
+					info!("past {at} already, holding gamma until waketime");
+				}
+			}
+		}
+	}
+	let busy = in_meeting(state, now);
+	let blackout = busy || config.blackout.iter().any(|w| w.contains(local));
+	// section still changes as usual for hooks and wallpapers, it's just the values that are the day's
+	let (values_of, redshift) = match blackout {
+		true => (DaySection::Day, 0.0),
+		false => (day_section, redshift),
+	};
+	let phase_override = config.overrides.get(values_of);
+	let reset = std::mem::take(&mut state.reset);
+	if reset {
+		if let Err(e) = state::clear_active_preset().and_then(|()| state::set_extreme(false)) {
+			warn!("failed to reset: {e:#}");
+		}
+		state.frozen = false;
+	}
+	let extreme = state::extreme();
+	// like leaving a preset, this has to be able to move gamma back up
+	let extreme_cleared = std::mem::replace(&mut state.extreme, extreme) && !extreme;
+	let target = {
+		let settings = scheduled_settings(values_of, redshift, config, extreme);
+		let brightness = match config.ambient.enabled {
+			true => ambient::blend(
+				settings.brightness,
+				ambient::read_lux(config.ambient.device.as_deref()),
+				&config.ambient,
+				config.brightness_range,
+			),
+			false => settings.brightness,
+		};
+		let brightness = match config.battery.enabled {
+			true => battery::scale(
+				brightness,
+				battery::read_charge(config.battery.device.as_deref()),
+				&config.battery,
+				config.brightness_range,
+			),
+			false => brightness,
+		};
+		let raw = DisplaySettings::new(settings.temperature, brightness);
+		let smoothed = match state.smoothed {
+			Some(previous) => previous.lerp(raw, config.smoothing),
+			None => raw,
+		};
+		state.smoothed = Some(smoothed);
+		let target = smoothed;
+		// what the section doesn't control stays as it was, or at day values if nothing was set yet
+		let controls = config.controls.get(day_section);
+		let held = state
+			.last_applied
+			.unwrap_or_else(|| DisplaySettings::new(config.temperature_bounds().1, config.brightness_range.1));
+		let pick = |controlled: bool, wanted: f32, held: f32| match controlled {
+			true => wanted,
+			false => held,
+		};
+		DisplaySettings::new(
+			pick(controls.temperature, target.temperature, held.temperature),
+			pick(controls.brightness, target.brightness, held.brightness),
+		)
+	};
+	let target = match args.max_temperature_change_per_cycle.filter(|&step| step > 0.0) {
+		Some(step) => limit_change(target, state.last_applied.or_else(|| read_back(backend, target.brightness).ok()), step),
+		None => target,
+	};
+	// added on top of what gets set, rather than decided on, so that it jitters around the level instead of ratcheting past it
+	let jitter = match redshift > 0.0 && config.controls.get(day_section).temperature {
+		true => dither(config.dither, rng),
+		false => 0.0,
+	};
+	info!(
+		now_shifted,
+		day_section = ?day_section,
+		redshift,
+		blackout,
+		busy,
+		temperature = target.temperature + jitter,
+		brightness = target.brightness,
+		"evaluated"
+	);
+
+	if state.section != Some(day_section) {
+		let crossed = match last_evaluated {
+			Some(last) if args.catch_up && now - last < chrono::Duration::days(1) => {
+				sections_between(last.time(), now.time(), &schedule(config, &args.schedule, now))
+			}
+			_ => state.section.into_iter().chain([day_section]).collect(),
+		};
+		// on startup there is nothing to have crossed from
+		let mut from = state.section;
+		for section in crossed.into_iter().skip(from.is_some() as usize) {
+			hooks::dispatch(&config.hooks, from, section);
+			from = Some(section);
+		}
+		if state.section.is_some() && day_section == DaySection::Morning && extreme {
+			info!("morning, leaving extreme mode");
+			if let Err(e) = state::set_extreme(false) {
+				warn!("failed to leave extreme mode: {e:#}");
+			}
+		}
+		if let Some(s) = args.fade_on_phase_change.filter(|&s| s > 0.0 && state.section.is_some()) {
+			state.phase_fade = Some(Duration::from_secs_f32(s));
+		}
+		if args.wallpapers && config.controls.get(day_section).wallpaper && !(state.section.is_none() && args.no_wallpaper_on_start) {
+			let (path, factor) = wallpaper_for(config, day_section, redshift);
+			change_wallpaper(config, state, path);
+			state.wallpaper_dim = Some(factor);
+		}
+		state.nightfall = day_section == DaySection::Night && state.section.is_some();
+		state.section = Some(day_section);
+		publish(state, None, Some(day_section));
+	} else if let (Some(darkest), Some(dim)) = (config.wallpapers.dim, state.wallpaper_dim) {
+		if config.controls.get(day_section).wallpaper && wallpaper::dim_factor(redshift, darkest) != dim {
+			let (path, factor) = wallpaper_for(config, day_section, redshift);
+			change_wallpaper(config, state, path);
+			state.wallpaper_dim = Some(factor);
+		}
+	}
+
+	if let Some(name) = requested_preset(config) {
+		if state.preset.as_ref() != Some(&name) {
+			if let Err(e) = apply(backend, state, clock, config.presets[&name]) {
+				warn!("failed to apply preset `{name}`: {e:#}");
+			}
+			state.preset = Some(name);
+		}
+		return;
+	}
+	// schedule has to be re-applied as is, preset could have been on either side of it
+	let reapply = state.preset.take().is_some() || extreme_cleared || reset;
+	// there is nothing to hold before the first set, like on starting past `freeze_after`
+	if state.frozen && !reapply && state.last_applied.is_some() {
+		debug!("skipped: frozen");
+		return;
+	}
+
+	if config.fullscreen.enabled && fullscreen::fullscreen_present(&config.fullscreen) {
+		if !state.suspended {
+			let day = DisplaySettings::new(config.temperature_bounds().1, config.brightness_range.1);
+			if let Err(e) = apply(backend, state, clock, day) {
+				warn!("failed to reset gamma for fullscreen: {e:#}");
+			}
+			state.suspended = true;
+		}
+		return;
+	}
+	state.suspended = false;
+
+	// past the night there is nothing to lower towards, so we set absolutely and restore whatever we left over from the night
+	let daytime = matches!(values_of, DaySection::Morning | DaySection::Day);
+	// a cooler day has to be set from the start, while a neutral one only when there is something to restore
+	let leftover = daytime && state.last_applied != Some(target) && (redshift != 0.0 || state.last_applied.is_some());
+	if redshift > 0.0 || reapply || phase_override.is_set() || leftover {
+		// overrides are absolute, so they have to be able to move gamma the other way too
+		let applied = match reapply || phase_override.is_set() || daytime || state.nightfall {
+			true => {
+				let target = DisplaySettings {
+					temperature: target.temperature + jitter,
+					..target
+				};
+				apply(backend, state, clock, target).map(|()| debug!("applied {target}"))
+			}
+			false => lower_gamma(backend, state, clock, target, jitter, config.invert_brightness),
+		};
+		match applied {
+			Ok(()) => state.nightfall = false,
+			Err(e) => warn!("failed to apply gamma, will try again next cycle: {e:#}"),
+		}
+	} else {
+		debug!("skipped: redshift is {redshift} ({values_of}), and there is nothing to set or restore");
+	}
+}
+
+/// `set_redshift` when there is a curve. Presets still take over, and wallpapers of the keyframes are set as they're reached
+fn follow_curve(config: &AppConfig, state: &mut DaemonState, backend: &mut dyn GammaBackend, curve: &Curve, clock: &dyn Clock) {
+	let local = clock.now().with_timezone(&Local).time();
+	let wallpaper = curve.wallpaper_at(local).map(Path::to_path_buf);
+	if wallpaper != state.curve_wallpaper {
+		if let Some(path) = &wallpaper {
+			let _ = cmd(config.wallpapers.command_for(&config.wallpapers.root.join(path)));
+		}
+		state.curve_wallpaper = wallpaper;
+	}
+	if let Some(name) = requested_preset(config) {
+		if state.preset.as_ref() != Some(&name) {
+			if let Err(e) = apply(backend, state, clock, config.presets[&name]) {
+				warn!("failed to apply preset `{name}`: {e:#}");
+			}
+			state.preset = Some(name);
+		}
+		return;
+	}
+	let reapply = state.preset.take().is_some() || std::mem::take(&mut state.reset);
+	let target = curve.at(local);
+	info!(temperature = target.temperature, brightness = target.brightness, "evaluated the curve");
+	if reapply || state.last_applied != Some(target) {
+		match apply(backend, state, clock, target) {
+			Ok(()) => debug!("applied {target}"),
+			Err(e) => warn!("failed to apply gamma, will try again next cycle: {e:#}"),
+		}
+	}
+}
+
+/// `target`, with temperature moved from `from` by no more than `step` Kelvin
+fn limit_change(target: DisplaySettings, from: Option<DisplaySettings>, step: f32) -> DisplaySettings {
+	let Some(from) = from else {
+		return target;
+	};
+	let temperature = target.temperature.clamp(from.temperature - step, from.temperature + step);
+	DisplaySettings { temperature, ..target }
+}
+
+/// Whether `at` came up on the clock going from `from` to `to`, wrapping past midnight
+fn crossed(at: NaiveTime, from: NaiveTime, to: NaiveTime) -> bool {
+	match from <= to {
+		true => from < at && at <= to,
+		false => at > from || at <= to,
+	}
+}
+
+/// Only ever moves gamma towards the warmer and dimmer end (brighter, if brightness is inverted).
+/// Sets `target` as is when there is nothing to compare against yet, like on starting in the evening.
+/// `jitter` goes on top of whatever is then set or held, so that it's never taken for a move of its own.
+fn lower_gamma(
+	backend: &mut dyn GammaBackend,
+	state: &mut DaemonState,
+	clock: &dyn Clock,
+	target: DisplaySettings,
+	jitter: f32,
+	invert_brightness: bool,
+) -> anyhow::Result<()> {
+	let jittered = |settings: DisplaySettings| DisplaySettings {
+		temperature: settings.temperature + jitter,
+		..settings
+	};
+	let current = match state.last_applied {
+		Some(_) => read_back(backend, target.brightness)
+			.inspect_err(|e| {
+				if !e.is::<NothingSet>() {
+					warn!("failed to read current gamma, setting {target} as is: {e:#}");
+				}
+			})
+			.ok(),
+		None => None,
+	};
+	let Some(current) = current else {
+		let sent = jittered(target);
+		apply(backend, state, clock, sent)?;
+		state.dithered = Some((target, sent));
+		debug!("applied {sent}, with nothing to lower from");
+		return Ok(());
+	};
+	// what was dithered last reads back off by its jitter, so it's held at what it was dithered from
+	let held = match state.dithered {
+		Some((base, sent)) if state.last_applied == Some(sent) && !drifted(current, sent) => base,
+		_ => current,
+	};
+	let brightness_moves = match invert_brightness {
+		_ if !backend.capabilities().brightness => true,
+		true => target.brightness > held.brightness,
+		false => target.brightness < held.brightness,
+	};
+	let moves = target.temperature < held.temperature && brightness_moves;
+	if !moves && jitter == 0.0 {
+		debug!("skipped: current {held} is already at or past {target}");
+		return Ok(());
+	}
+	let base = match moves {
+		true => target,
+		false => held,
+	};
+	let sent = jittered(base);
+	apply(backend, state, clock, sent)?;
+	state.dithered = Some((base, sent));
+	debug!("applied {sent}");
+	Ok(())
+}
+
+/// Random offset within `±bound` Kelvin, to mask banding on flat warm values
+fn dither(bound: f32, rng: &mut dyn RngCore) -> f32 {
+	if bound <= 0.0 {
+		return 0.0;
+	}
+	rng.gen_range(-bound..=bound)
 }
@@ -0,0 +1,143 @@
+use std::process::Command;
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::require_binary;
+
+/// Which outputs gamma is applied to, by name (eg `eDP-1`)
+#[derive(Clone, Debug, Default)]
+pub struct OutputFilter {
+	include: Vec<Regex>,
+	exclude: Vec<Regex>,
+}
+impl OutputFilter {
+	/// Patterns are anchored regexes, so `eDP-.*` won't match `xeDP-1`
+	pub fn new(include: &[String], exclude: &[String]) -> Result<Self> {
+		let compile = |patterns: &[String]| -> Result<Vec<Regex>> {
+			patterns
+				.iter()
+				.map(|p| Regex::new(&format!("^(?:{p})$")).with_context(|| format!("bad output pattern `{p}`")))
+				.collect()
+		};
+		Ok(Self {
+			include: compile(include)?,
+			exclude: compile(exclude)?,
+		})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.include.is_empty() && self.exclude.is_empty()
+	}
+
+	/// Everything is included if no `include` patterns were given
+	pub fn matches(&self, name: &str) -> bool {
+		(self.include.is_empty() || self.include.iter().any(|r| r.is_match(name))) && !self.exclude.iter().any(|r| r.is_match(name))
+	}
+}
+
+/// A display, as the compositor reports it
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Output {
+	pub name: String,
+	#[serde(default)]
+	pub make: String,
+	#[serde(default)]
+	pub model: String,
+	/// Off, or disabled in the compositor's config
+	#[serde(default = "Output::default_active")]
+	pub active: bool,
+	pub current_mode: Option<Mode>,
+	/// Whether the display is powered, as older sway puts it
+	pub dpms: Option<bool>,
+	/// Same, as newer sway puts it
+	pub power: Option<bool>,
+}
+impl Output {
+	fn default_active() -> bool {
+		true
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Mode {
+	pub width: u32,
+	pub height: u32,
+}
+
+/// Takes what `swaymsg -t get_outputs -r` prints
+pub fn parse_sway(json: &str) -> Result<Vec<Output>> {
+	serde_json::from_str(json).context("unexpected output of `swaymsg -t get_outputs`")
+}
+
+/// Every output the compositor knows about, plugged in or not
+pub fn query() -> Result<Vec<Output>> {
+	require_binary("swaymsg", "install sway; other compositors can't be asked yet")?;
+	let output = Command::new("swaymsg")
+		.args(["-t", "get_outputs", "-r"])
+		.output()
+		.context("failed to run swaymsg")?;
+	if !output.status.success() {
+		bail!(
+			"no compositor to ask, is sway running? swaymsg says: {}",
+			String::from_utf8_lossy(&output.stderr).trim()
+		);
+	}
+	parse_sway(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Names of the active outputs that pass `filter`, sorted
+pub fn targeted(outputs: &[Output], filter: &OutputFilter) -> Vec<String> {
+	let mut names: Vec<String> = outputs.iter().filter(|o| o.active && filter.matches(&o.name)).map(|o| o.name.clone()).collect();
+	names.sort();
+	names
+}
+
+/// Outputs that came and went between two lists of them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Change {
+	pub added: Vec<String>,
+	pub removed: Vec<String>,
+}
+impl Change {
+	pub fn between(before: &[String], after: &[String]) -> Self {
+		let missing_from = |list: &[String], other: &[String]| list.iter().filter(|name| !other.contains(name)).cloned().collect();
+		Self {
+			added: missing_from(after, before),
+			removed: missing_from(before, after),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty()
+	}
+}
+
+/// Whether every active output has its display powered down, so that there is nothing to set gamma for
+pub fn all_off(outputs: &[Output]) -> bool {
+	let mut active = outputs.iter().filter(|o| o.active).peekable();
+	active.peek().is_some() && active.all(|o| o.dpms == Some(false) || o.power == Some(false))
+}
+
+/// One row per output, with whether `filter` lets gamma be set on it
+pub fn render(outputs: &[Output], filter: &OutputFilter) -> String {
+	let mut table = format!("{:<12} {:<32} {:<11} {}\n", "name", "make and model", "resolution", "gamma");
+	for o in outputs {
+		let resolution = match (o.active, o.current_mode) {
+			(true, Some(mode)) => format!("{}x{}", mode.width, mode.height),
+			_ => "off".to_owned(),
+		};
+		let gamma = match filter.matches(&o.name) {
+			true => "set",
+			false => "excluded",
+		};
+		table.push_str(&format!(
+			"{:<12} {:<32} {:<11} {gamma}\n",
+			o.name,
+			format!("{} {}", o.make, o.model).trim(),
+			resolution
+		));
+	}
+	table
+}
@@ -0,0 +1,168 @@
+use std::fmt::Write;
+
+use chrono::{Duration, NaiveTime};
+use serde::Serialize;
+
+use crate::{
+	config::AppConfig,
+	display::{settings_for, DisplaySettings},
+	schedule::{evaluate_range, Schedule, TimeEvaluation},
+};
+
+pub const CSV_HEADER: &str = "time,section,redshift,temperature,brightness";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+	/// Aligned columns, for reading
+	#[default]
+	Table,
+	/// Tab-separated, with a header
+	Tsv,
+	/// Array of objects
+	Json,
+}
+
+/// What the schedule comes down to at `time`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Row {
+	#[serde(serialize_with = "serialize_minutes")]
+	pub time: NaiveTime,
+	#[serde(flatten)]
+	pub evaluation: TimeEvaluation,
+	#[serde(flatten)]
+	pub settings: DisplaySettings,
+}
+
+fn serialize_minutes<S: serde::Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+	serializer.collect_str(&time.format("%H:%M"))
+}
+
+/// Full day starting from `start`, one row per `step`
+pub fn rows(schedule: &Schedule, start: NaiveTime, step: Duration, config: &AppConfig) -> Vec<Row> {
+	evaluate_range(start, start, step, schedule)
+		.into_iter()
+		.map(|(time, evaluation)| Row {
+			time,
+			evaluation,
+			settings: settings_for(evaluation.day_section, evaluation.redshift, config),
+		})
+		.collect()
+}
+
+/// Full day starting from midnight, one row per `step`
+pub fn to_csv(schedule: &Schedule, step: Duration, config: &AppConfig) -> String {
+	delimited(&rows(schedule, NaiveTime::MIN, step, config), ",")
+}
+
+pub fn render(rows: &[Row], format: OutputFormat) -> String {
+	match format {
+		OutputFormat::Table => {
+			let mut table = format!("{:<6} {:<8} {:>8} {:>11} {:>10}\n", "time", "section", "redshift", "temperature", "brightness");
+			for row in rows {
+				writeln!(
+					table,
+					"{:<6} {:<8} {:>8.2} {:>10.0}K {:>10.2}",
+					row.time.format("%H:%M").to_string(),
+					row.evaluation.day_section.to_string(),
+					row.evaluation.redshift,
+					row.settings.temperature,
+					row.settings.brightness
+				)
+				.unwrap();
+			}
+			table
+		}
+		OutputFormat::Tsv => delimited(rows, "\t"),
+		OutputFormat::Json => serde_json::to_string_pretty(rows).unwrap() + "\n",
+	}
+}
+
+fn delimited(rows: &[Row], separator: &str) -> String {
+	let mut out = format!("{}\n", CSV_HEADER.replace(',', separator));
+	for row in rows {
+		writeln!(
+			out,
+			"{}{separator}{}{separator}{:.2}{separator}{:.0}{separator}{:.2}",
+			row.time.format("%H:%M"),
+			row.evaluation.day_section,
+			row.evaluation.redshift,
+			row.settings.temperature,
+			row.settings.brightness
+		)
+		.unwrap();
+	}
+	out
+}
+
+/// Temperature, then brightness, as columns of block characters `height` lines tall, one column per row, with the times under them
+pub fn graph(rows: &[Row], height: usize) -> String {
+	let temperatures: Vec<f32> = rows.iter().map(|r| r.settings.temperature).collect();
+	let brightnesses: Vec<f32> = rows.iter().map(|r| r.settings.brightness).collect();
+	let labels = |values: &[f32], format: fn(f32) -> String| {
+		let (min, max) = bounds(values);
+		(format(min), format(max))
+	};
+	let temperature_labels = labels(&temperatures, |v| format!("{v:.0}K"));
+	let brightness_labels = labels(&brightnesses, |v| format!("{v:.2}"));
+	let gutter = [&temperature_labels.0, &temperature_labels.1, &brightness_labels.0, &brightness_labels.1]
+		.iter()
+		.map(|l| l.chars().count())
+		.max()
+		.unwrap_or_default()
+		+ 1;
+
+	let mut out = String::new();
+	plot(&mut out, &temperatures, temperature_labels, gutter, height);
+	out.push('\n');
+	plot(&mut out, &brightnesses, brightness_labels, gutter, height);
+
+	// a time under every few columns, as many as fit
+	const LABEL: usize = "00:00".len();
+	let mut axis = vec![' '; rows.len()];
+	for column in (0..rows.len()).step_by((LABEL + 1).max(rows.len() / 4)).filter(|c| c + LABEL <= rows.len()) {
+		for (i, c) in rows[column].time.format("%H:%M").to_string().chars().enumerate() {
+			axis[column + i] = c;
+		}
+	}
+	let axis: String = axis.into_iter().collect();
+	writeln!(out, "{}{}", " ".repeat(gutter), axis.trim_end()).unwrap();
+	out
+}
+
+fn bounds(values: &[f32]) -> (f32, f32) {
+	values
+		.iter()
+		.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &v| (min.min(v), max.max(v)))
+}
+
+/// Lines of eighth blocks, the top one labelled with `max` and the bottom one with `min`, which still gets the lowest block. A flat series is drawn half way up
+fn plot(out: &mut String, values: &[f32], (min_label, max_label): (String, String), gutter: usize, height: usize) {
+	const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+	let (min, max) = bounds(values);
+	let levels: Vec<f32> = values
+		.iter()
+		.map(|&v| match max > min {
+			true => 0.125 + (v - min) / (max - min) * (height as f32 - 0.125),
+			false => height as f32 / 2.0,
+		})
+		.collect();
+	for line in (0..height).rev() {
+		let label = match line {
+			_ if line == height - 1 => &max_label,
+			0 => &min_label,
+			_ => "",
+		};
+		let bars: String = levels
+			.iter()
+			.map(|level| {
+				let filled = level - line as f32;
+				match filled {
+					_ if filled >= 1.0 => '█',
+					_ if filled * 8.0 < 1.0 => ' ',
+					_ => BLOCKS[(filled * 8.0) as usize - 1],
+				}
+			})
+			.collect();
+		writeln!(out, "{label:>width$} {}", bars.trim_end(), width = gutter - 1).unwrap();
+	}
+}
@@ -0,0 +1,45 @@
+use std::{
+	io::{BufRead, BufReader},
+	process::{Command, Stdio},
+	sync::mpsc,
+	thread,
+};
+
+use anyhow::{Context, Result};
+
+use crate::require_binary;
+
+/// Sends on the returned channel every time the machine wakes up from suspend, going by logind's `PrepareForSleep`
+pub fn listen() -> Result<mpsc::Receiver<()>> {
+	let gdbus = require_binary("gdbus", "install glib2, or drop --apply-on-resume")?;
+	let mut child = Command::new(gdbus)
+		.args([
+			"monitor",
+			"--system",
+			"--dest",
+			"org.freedesktop.login1",
+			"--object-path",
+			"/org/freedesktop/login1",
+		])
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.context("failed to start `gdbus monitor`")?;
+	let stdout = child.stdout.take().unwrap();
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || {
+		for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+			if is_resume(&line) && tx.send(()).is_err() {
+				break;
+			}
+		}
+		let _ = child.kill();
+		tracing::warn!("`gdbus monitor` exited, no longer watching for resumes");
+	});
+	Ok(rx)
+}
+
+/// `PrepareForSleep(false)` is sent on the way back up, `true` on the way down
+pub fn is_resume(line: &str) -> bool {
+	line.contains("org.freedesktop.login1.Manager.PrepareForSleep") && line.contains("(false")
+}
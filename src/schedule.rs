@@ -0,0 +1,501 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::error::Error;
+
+use chrono::{Duration, NaiveTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, derive_new::new, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Waketime {
+	pub hours: u32,
+	pub minutes: u32,
+	pub seconds: u32,
+}
+impl FromStr for Waketime {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Error> {
+		NaiveTime::parse_from_str(s, "%H:%M")
+			.or_else(|_| NaiveTime::parse_from_str(s, "%H:%M:%S"))
+			.map(Self::from)
+			.map_err(|e| Error::parse("waketime", s, format!("{e}, expected HH:MM or HH:MM:SS")))
+	}
+}
+impl From<Waketime> for String {
+	fn from(w: Waketime) -> Self {
+		w.to_string()
+	}
+}
+impl TryFrom<String> for Waketime {
+	type Error = Error;
+
+	fn try_from(s: String) -> Result<Self, Error> {
+		s.parse()
+	}
+}
+impl From<NaiveTime> for Waketime {
+	fn from(t: NaiveTime) -> Self {
+		Self::new(t.hour(), t.minute(), t.second())
+	}
+}
+impl Waketime {
+	pub fn as_seconds(&self) -> u32 {
+		self.hours * 3600 + self.minutes * 60 + self.seconds
+	}
+
+	/// Moved by `minutes`, either way, wrapping around midnight
+	pub fn shifted(self, minutes: i32) -> Self {
+		let seconds = (self.as_seconds() as i64 + minutes as i64 * 60).rem_euclid(24 * 3600) as u32;
+		Self::new(seconds / 3600, seconds / 60 % 60, seconds % 60)
+	}
+}
+/// Same `HH:MM` it's parsed from, with seconds only if there are any
+impl fmt::Display for Waketime {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:02}:{:02}", self.hours, self.minutes)?;
+		if self.seconds != 0 {
+			write!(f, ":{:02}", self.seconds)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DaySection {
+	Morning,
+	Day,
+	Evening,
+	Night,
+}
+
+impl fmt::Display for DaySection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let s = match self {
+			Self::Morning => "morning",
+			Self::Day => "day",
+			Self::Evening => "evening",
+			Self::Night => "night",
+		};
+		f.write_str(s)
+	}
+}
+
+/// Where each section starts, in hours after waketime. Morning wraps around midnight into the first hours after waking.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionBounds {
+	pub day: f32,
+	pub evening: f32,
+	pub night: f32,
+	pub morning: f32,
+}
+impl Default for SectionBounds {
+	fn default() -> Self {
+		Self {
+			day: 2.5,
+			evening: 10.5,
+			night: 16.0,
+			morning: 20.0,
+		}
+	}
+}
+
+/// Redshift each part of the day settles on, from 0 to 20, or from -20 for the day
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RedshiftLevels {
+	/// Where the evening ramp ends up, right before night starts
+	pub evening_end: f32,
+	/// Held through the night, and where the morning ramps start from
+	pub night: f32,
+	/// Held through the morning and day, once the ramps are done. Negative cools, positive stays a little warm
+	pub day: f32,
+}
+impl Default for RedshiftLevels {
+	fn default() -> Self {
+		Self {
+			evening_end: 20.0,
+			night: 20.0,
+			day: 0.0,
+		}
+	}
+}
+
+/// Span of the day given as `HH:MM-HH:MM`, start inclusive, wrapping past midnight if it ends before it starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeWindow {
+	pub start: NaiveTime,
+	pub end: NaiveTime,
+}
+impl TimeWindow {
+	pub fn contains(&self, time: NaiveTime) -> bool {
+		match self.start <= self.end {
+			true => self.start <= time && time < self.end,
+			false => time >= self.start || time < self.end,
+		}
+	}
+}
+impl FromStr for TimeWindow {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> anyhow::Result<Self> {
+		let parse = |t: &str| {
+			NaiveTime::parse_from_str(t.trim(), "%H:%M")
+				.or_else(|_| NaiveTime::parse_from_str(t.trim(), "%H:%M:%S"))
+				.with_context(|| format!("bad time {t:?} in window {s:?}"))
+		};
+		let (start, end) = s.split_once('-').ok_or_else(|| anyhow!("expected `HH:MM-HH:MM`, got {s:?}"))?;
+		Ok(Self {
+			start: parse(start)?,
+			end: parse(end)?,
+		})
+	}
+}
+impl fmt::Display for TimeWindow {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}-{}", self.start.format("%H:%M"), self.end.format("%H:%M"))
+	}
+}
+impl From<TimeWindow> for String {
+	fn from(w: TimeWindow) -> Self {
+		w.to_string()
+	}
+}
+impl TryFrom<String> for TimeWindow {
+	type Error = anyhow::Error;
+
+	fn try_from(s: String) -> anyhow::Result<Self> {
+		s.parse()
+	}
+}
+
+/// Signed span like `+30m`, `-1h` or `1h30m`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Offset {
+	pub seconds: i32,
+}
+impl FromStr for Offset {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> anyhow::Result<Self> {
+		let trimmed = s.trim();
+		let (sign, rest) = match trimmed.strip_prefix('-') {
+			Some(rest) => (-1, rest),
+			None => (1, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+		};
+		if rest.is_empty() {
+			bail!("expected an offset like `+30m` or `-1h`, got {s:?}");
+		}
+		let (mut seconds, mut number) = (0, String::new());
+		for c in rest.chars() {
+			match c {
+				c if c.is_ascii_digit() => number.push(c),
+				unit => {
+					let n: i32 = number.parse().with_context(|| format!("expected a number before `{unit}` in {s:?}"))?;
+					number.clear();
+					seconds += n * match unit {
+						'h' => 3600,
+						'm' => 60,
+						's' => 1,
+						_ => bail!("unknown unit `{unit}` in {s:?}, expected h, m or s"),
+					};
+				}
+			}
+		}
+		if !number.is_empty() {
+			bail!("{s:?} is missing a unit after {number}");
+		}
+		Ok(Self { seconds: sign * seconds })
+	}
+}
+impl fmt::Display for Offset {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let sign = if self.seconds < 0 { '-' } else { '+' };
+		let s = self.seconds.unsigned_abs();
+		write!(f, "{sign}")?;
+		let parts = [(s / 3600, 'h'), (s % 3600 / 60, 'm'), (s % 60, 's')];
+		let mut wrote = false;
+		for (n, unit) in parts.into_iter().filter(|(n, _)| *n > 0) {
+			write!(f, "{n}{unit}")?;
+			wrote = true;
+		}
+		if !wrote {
+			write!(f, "0m")?;
+		}
+		Ok(())
+	}
+}
+impl From<Offset> for String {
+	fn from(o: Offset) -> Self {
+		o.to_string()
+	}
+}
+impl TryFrom<String> for Offset {
+	type Error = anyhow::Error;
+
+	fn try_from(s: String) -> anyhow::Result<Self> {
+		s.parse()
+	}
+}
+
+/// Another wake period within the same day, like after an afternoon nap
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WakePeriod {
+	pub waketime: NaiveTime,
+	/// Length of this period's evening ramp
+	pub n_hours: f32,
+}
+
+/// Everything `evaluate_time` needs to know about the user's day
+#[derive(Clone, Debug, Default, PartialEq, derive_new::new)]
+pub struct Schedule {
+	pub waketime: Waketime,
+	/// Length of the evening ramp, which always ends at the start of the night
+	pub n_hours: f32,
+	pub bounds: SectionBounds,
+	/// Right after waketime, ease from the night values to the day ones over this many minutes, instead of snapping
+	pub grace_minutes: f32,
+	/// Length of the morning ramp, starting at waketime. Equal to `n_hours` makes the decline mirror the evening ascent; 0 to disable
+	pub morning_n_hours: f32,
+	pub levels: RedshiftLevels,
+	/// Wake periods besides the one starting at `waketime`. Each runs until the next one starts, with `bounds` scaled down to fit
+	#[new(default)]
+	pub wake_periods: Vec<WakePeriod>,
+	/// Moves everything later by this much, or earlier if negative
+	#[new(default)]
+	pub offset: Offset,
+}
+impl Schedule {
+	pub fn with_wake_periods(mut self, wake_periods: Vec<WakePeriod>) -> Self {
+		self.wake_periods = wake_periods;
+		self
+	}
+
+	pub fn with_offset(mut self, offset: Offset) -> Self {
+		self.offset = offset;
+		self
+	}
+
+	/// Waketime, evening ramp length and section bounds of the wake period `now` falls into
+	fn period_at(&self, now: NaiveTime) -> (Waketime, f32, SectionBounds) {
+		if self.wake_periods.is_empty() {
+			return (self.waketime, self.n_hours, self.bounds);
+		}
+		let mut periods: Vec<(Waketime, f32)> = self
+			.wake_periods
+			.iter()
+			.map(|p| (Waketime::from(p.waketime), p.n_hours))
+			.chain([(self.waketime, self.n_hours)])
+			.collect();
+		periods.sort_by_key(|(w, _)| w.as_seconds());
+		let ns = now.num_seconds_from_midnight();
+		// last one to have started, which is the latest of yesterday if none did today yet
+		let i = periods.iter().rposition(|(w, _)| w.as_seconds() <= ns).unwrap_or(periods.len() - 1);
+		let (waketime, n_hours) = periods[i];
+		let next = periods[(i + 1) % periods.len()].0;
+		let length_s = match next.as_seconds() as i32 - waketime.as_seconds() as i32 {
+			d if d <= 0 => d + 24 * 3600,
+			d => d,
+		};
+		let scale = length_s as f32 / (24.0 * 3600.0);
+		let bounds = SectionBounds {
+			day: self.bounds.day * scale,
+			evening: self.bounds.evening * scale,
+			night: self.bounds.night * scale,
+			morning: self.bounds.morning * scale,
+		};
+		(waketime, n_hours, bounds)
+	}
+
+	fn grace_s(&self) -> i32 {
+		(self.grace_minutes.max(0.0) * 60.0) as i32
+	}
+
+	fn morning_s(&self) -> i32 {
+		(self.morning_n_hours.max(0.0) * 3600.0) as i32
+	}
+
+	/// When, on the clock `evaluate_time` is given, each part of the day starts
+	/// Of the wake period starting at `waketime`, if there are several
+	pub fn transitions(&self) -> Transitions {
+		self.transitions_of(self.period_at(NaiveTime::MIN + Duration::seconds(self.waketime.as_seconds() as i64)))
+	}
+
+	/// Like `transitions`, of the wake period `now` falls into
+	pub fn transitions_at(&self, now: NaiveTime) -> Transitions {
+		self.transitions_of(self.period_at(now - Duration::seconds(self.offset.seconds as i64)))
+	}
+
+	fn transitions_of(&self, (waketime, n_hours, bounds): (Waketime, f32, SectionBounds)) -> Transitions {
+		let at = |hours: f32| NaiveTime::MIN + Duration::seconds(waketime.as_seconds() as i64 + self.offset.seconds as i64 + (hours * 3600.0) as i64);
+		Transitions {
+			morning: at(bounds.morning),
+			day: at(bounds.day),
+			evening: at(bounds.evening),
+			evening_ramp: at(bounds.evening.max(bounds.night - n_hours)),
+			night: at(bounds.night),
+		}
+	}
+
+	/// Whether we're still easing out of the night values after waketime, be it through the grace or the morning ramp
+	pub fn in_grace(&self, now: NaiveTime) -> bool {
+		evaluate_time(now, self).now_shifted < self.grace_s().max(self.morning_s())
+	}
+}
+
+/// Clock times of the section boundaries. Each section takes over right after its time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transitions {
+	pub morning: NaiveTime,
+	pub day: NaiveTime,
+	pub evening: NaiveTime,
+	/// Where redshift starts going up. Later than `evening` if the ramp is shorter than the evening is
+	pub evening_ramp: NaiveTime,
+	pub night: NaiveTime,
+}
+
+/// Why `now` comes out at the redshift it does, as a sentence without its full stop, like
+/// "It's 19:30, 13h30m after waketime 06:00, which is in the evening section; the evening ramp started at 18:00 and reaches full night in 4h, so redshift is 7.50"
+pub fn explain(now: NaiveTime, schedule: &Schedule) -> String {
+	let TimeEvaluation {
+		now_shifted,
+		day_section,
+		redshift,
+	} = evaluate_time(now, schedule);
+	let t = schedule.transitions_at(now);
+	let (waketime, n_hours, bounds) = schedule.period_at(now - Duration::seconds(schedule.offset.seconds as i64));
+	let ramp_started = now_shifted as f32 >= bounds.evening.max(bounds.night - n_hours) * 3600.0;
+	let hm = |time: NaiveTime| time.format("%H:%M").to_string();
+	let until = |time: NaiveTime| span((time - now).num_seconds().rem_euclid(24 * 3600));
+	let why = match day_section {
+		DaySection::Morning if now_shifted as f32 > bounds.morning * 3600.0 => format!("waketime is in {}", span(24 * 3600 - now_shifted as i64)),
+		DaySection::Morning | DaySection::Day if now_shifted < schedule.grace_s().max(schedule.morning_s()) => "still easing out of the night".to_owned(),
+		DaySection::Morning | DaySection::Day => format!("the evening ramp starts at {}", hm(t.evening_ramp)),
+		DaySection::Evening if !ramp_started => format!("the evening ramp starts at {}", hm(t.evening_ramp)),
+		DaySection::Evening => format!(
+			"the evening ramp started at {} and reaches full night in {}",
+			hm(t.evening_ramp),
+			until(t.night)
+		),
+		DaySection::Night => format!("night holds until {}, in {}", hm(t.morning), until(t.morning)),
+	};
+	format!(
+		"It's {}, {} after waketime {}, which is in the {day_section} section; {why}, so redshift is {redshift:.2}",
+		hm(now),
+		span(now_shifted as i64),
+		waketime,
+	)
+}
+
+/// Like `13h30m`, `4h` or `45m`
+fn span(seconds: i64) -> String {
+	let (hours, minutes) = (seconds / 3600, seconds / 60 % 60);
+	match (hours, minutes) {
+		(0, m) => format!("{m}m"),
+		(h, 0) => format!("{h}h"),
+		(h, m) => format!("{h}h{m}m"),
+	}
+}
+
+/// Redshift `progress` hours into a ramp that takes `n_hours` to go from 0 to `max`, as the evening one always went: in whole twentieths
+/// of `max`, running an eighth of `n_hours` ahead, so that with the default 4h it goes up by a step every 12 minutes, from 2 steps right as it starts.
+/// Clamped on both ends.
+pub fn ramp(progress: f32, n_hours: f32, max: f32) -> f32 {
+	match progress > 0.0 {
+		true => ((progress + n_hours / 8.0) * (20.0 / n_hours)).floor().min(20.0) * (max / 20.0),
+		false => 0.0,
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct TimeEvaluation {
+	/// seconds since waketime
+	pub now_shifted: i32,
+	pub day_section: DaySection,
+	/// from 0 to 20, negative when the day cools
+	pub redshift: f32,
+}
+
+pub fn evaluate_time(now: NaiveTime, schedule: &Schedule) -> TimeEvaluation {
+	// moving the schedule later is the same as the clock running behind
+	let now = now - Duration::seconds(schedule.offset.seconds as i64);
+	let (waketime, n_hours, bounds) = schedule.period_at(now);
+	let ns = now.num_seconds_from_midnight();
+	let wt = waketime.as_seconds();
+
+	// shift everything wt seconds back
+	// in python would be `(ns - wt) % 24`, but rust doesn't want to exhibit desired behaviour with % on negative numbers
+	let mut now_shifted = ns as i32 - wt as i32;
+	if now_shifted < 0 {
+		now_shifted += 24 * 3600;
+	}
+
+	let h = |hours: f32| (hours * 3600.0) as i32;
+	let day_section = match now_shifted {
+		t if (t > h(bounds.morning)) || (t <= h(bounds.day)) => DaySection::Morning,
+		t if t <= h(bounds.evening) => DaySection::Day,
+		t if t <= h(bounds.night) => DaySection::Evening,
+		_ => DaySection::Night,
+	};
+
+	// hours into a ramp, counted off in whole seconds, so that both ways come out the same at mirrored times
+	let hours_from = |from_s: i32, to_s: i32| (to_s - from_s) as f32 / 3600.0;
+	let evening_end = schedule.levels.evening_end.clamp(0.0, 20.0);
+	let night = schedule.levels.night.clamp(0.0, 20.0);
+	let day = schedule.levels.day.clamp(-20.0, 20.0);
+	// morning ramps run backwards from waketime, so that they mirror the evening one
+	let grace = (now_shifted < schedule.grace_s()).then(|| {
+		let grace_hours = schedule.grace_minutes / 60.0;
+		ramp(hours_from(now_shifted, schedule.grace_s()), grace_hours, night)
+	});
+	let morning = (now_shifted < schedule.morning_s()).then(|| ramp(hours_from(now_shifted, schedule.morning_s()), schedule.morning_n_hours, night));
+	let redshift = match day_section {
+		DaySection::Morning | DaySection::Day => grace.into_iter().chain(morning).fold(day, f32::max),
+		// a warm day is held until the ramp passes it, rather than jumping back up to 0
+		DaySection::Evening => ramp(hours_from(h(bounds.night - n_hours), now_shifted), n_hours, evening_end).max(day),
+		DaySection::Night => night,
+	};
+
+	TimeEvaluation {
+		now_shifted,
+		day_section,
+		redshift,
+	}
+}
+
+/// Every section passed through over `[start, end]`, in order, with repeats dropped. Resolution is a minute.
+pub fn sections_between(start: NaiveTime, end: NaiveTime, schedule: &Schedule) -> Vec<DaySection> {
+	let mut sections: Vec<DaySection> = Vec::new();
+	let passed = evaluate_range(start, end, Duration::minutes(1), schedule)
+		.into_iter()
+		.map(|(_, e)| e.day_section);
+	for section in passed.chain([evaluate_time(end, schedule).day_section]) {
+		if sections.last() != Some(&section) {
+			sections.push(section);
+		}
+	}
+	sections
+}
+
+/// Evaluates every `step` over the half-open window `[start, end)`. If `end <= start`, the window wraps past midnight, so `start == end` covers the full day.
+pub fn evaluate_range(start: NaiveTime, end: NaiveTime, step: Duration, schedule: &Schedule) -> Vec<(NaiveTime, TimeEvaluation)> {
+	assert!(step > Duration::zero(), "step must be positive");
+	let mut span = end - start;
+	if span <= Duration::zero() {
+		span += Duration::days(1);
+	}
+
+	let mut out = Vec::new();
+	let mut elapsed = Duration::zero();
+	while elapsed < span {
+		let t = start + elapsed;
+		out.push((t, evaluate_time(t, schedule)));
+		elapsed += step;
+	}
+	out
+}
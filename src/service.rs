@@ -0,0 +1,69 @@
+use std::{fmt::Write, path::Path};
+
+/// What to write a service definition for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum InitSystem {
+	/// User unit, for `~/.config/systemd/user/auto_redshift.service`
+	Systemd,
+	/// `run` script, for a service directory under runsvdir
+	Runit,
+}
+
+/// Service running `binary --config <config> start <start_args>`
+pub fn render(init: InitSystem, binary: &Path, config: &Path, start_args: &[String]) -> String {
+	let argv: Vec<String> = [
+		binary.display().to_string(),
+		"--config".to_owned(),
+		config.display().to_string(),
+		"start".to_owned(),
+	]
+	.into_iter()
+	.chain(start_args.iter().cloned())
+	.collect();
+	let mut out = String::new();
+	match init {
+		InitSystem::Systemd => {
+			let exec: Vec<String> = argv.iter().map(|a| systemd_quote(a)).collect();
+			writeln!(out, "[Unit]").unwrap();
+			writeln!(out, "Description=Screen temperature and brightness following the time of day").unwrap();
+			// gamma can only be set once there is a compositor to set it on
+			writeln!(out, "PartOf=graphical-session.target").unwrap();
+			writeln!(out, "After=graphical-session.target").unwrap();
+			writeln!(out).unwrap();
+			writeln!(out, "[Service]").unwrap();
+			writeln!(out, "ExecStart={}", exec.join(" ")).unwrap();
+			writeln!(out, "Restart=on-failure").unwrap();
+			writeln!(out).unwrap();
+			writeln!(out, "[Install]").unwrap();
+			writeln!(out, "WantedBy=graphical-session.target").unwrap();
+		}
+		InitSystem::Runit => {
+			let exec: Vec<String> = argv.iter().map(|a| sh_quote(a)).collect();
+			writeln!(out, "#!/bin/sh").unwrap();
+			writeln!(
+				out,
+				"# needs WAYLAND_DISPLAY (or DISPLAY) and DBUS_SESSION_BUS_ADDRESS of the session, which runsvdir doesn't pass on by itself"
+			)
+			.unwrap();
+			writeln!(out, "exec 2>&1").unwrap();
+			writeln!(out, "exec {}", exec.join(" ")).unwrap();
+		}
+	}
+	out
+}
+
+/// Double quotes only when needed, with `%` doubled so that systemd doesn't take it for a specifier
+fn systemd_quote(arg: &str) -> String {
+	let arg = arg.replace('%', "%%");
+	match arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';')) {
+		true => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+		false => arg,
+	}
+}
+
+fn sh_quote(arg: &str) -> String {
+	match !arg.is_empty() && arg.chars().all(|c| c.is_ascii_alphanumeric() || "-_./:=@+,".contains(c)) {
+		true => arg.to_owned(),
+		false => format!("'{}'", arg.replace('\'', r"'\''")),
+	}
+}
@@ -0,0 +1,78 @@
+//! Bits of daemon state that live on disk, so that they can be set from another process and survive restarts
+
+use std::{fs, io, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::display::DisplaySettings;
+
+/// `$XDG_STATE_HOME/auto_redshift`, falling back to `~/.local/state/auto_redshift`
+pub fn state_dir() -> PathBuf {
+	let base = std::env::var_os("XDG_STATE_HOME")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".local/state"));
+	base.join("auto_redshift")
+}
+
+fn preset_path() -> PathBuf {
+	state_dir().join("preset")
+}
+
+fn last_applied_path() -> PathBuf {
+	state_dir().join("last_applied")
+}
+
+/// What the last one-shot `apply` set, stored as `<temperature> <brightness>`
+pub fn last_applied() -> Option<DisplaySettings> {
+	let s = fs::read_to_string(last_applied_path()).ok()?;
+	let (temperature, brightness) = s.trim().split_once(' ')?;
+	Some(DisplaySettings::new(temperature.parse().ok()?, brightness.parse().ok()?))
+}
+
+pub fn set_last_applied(settings: DisplaySettings) -> Result<()> {
+	let path = last_applied_path();
+	fs::create_dir_all(path.parent().unwrap())?;
+	fs::write(&path, format!("{} {}", settings.temperature, settings.brightness)).with_context(|| format!("failed to write {}", path.display()))
+}
+
+fn extreme_path() -> PathBuf {
+	state_dir().join("extreme")
+}
+
+/// Whether the warm end is pushed past `temperature_range` for the night
+pub fn extreme() -> bool {
+	extreme_path().exists()
+}
+
+pub fn set_extreme(on: bool) -> Result<()> {
+	let path = extreme_path();
+	match on {
+		true => {
+			fs::create_dir_all(path.parent().unwrap())?;
+			fs::write(&path, "").with_context(|| format!("failed to write {}", path.display()))
+		}
+		false => match fs::remove_file(&path) {
+			Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+			_ => Ok(()),
+		},
+	}
+}
+
+pub fn active_preset() -> Option<String> {
+	let name = fs::read_to_string(preset_path()).ok()?;
+	let name = name.trim();
+	(!name.is_empty()).then(|| name.to_owned())
+}
+
+pub fn set_active_preset(name: &str) -> Result<()> {
+	let path = preset_path();
+	fs::create_dir_all(path.parent().unwrap())?;
+	fs::write(&path, name).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn clear_active_preset() -> Result<()> {
+	match fs::remove_file(preset_path()) {
+		Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+		_ => Ok(()),
+	}
+}
@@ -0,0 +1,35 @@
+use crate::schedule::Waketime;
+
+pub const N_HOURS_STEP: f32 = 0.5;
+pub const WAKETIME_STEP_MINUTES: i32 = 15;
+pub const HELP: &str = "[+/-] n_hours, [</>] waketime, [a]pply to the screen, [q]uit";
+
+/// Schedule parameters being tuned
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Params {
+	pub n_hours: f32,
+	pub waketime: Waketime,
+}
+
+/// What a key asks for, beyond changing `Params`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+	Changed,
+	Apply,
+	Quit,
+	Unknown,
+}
+
+/// `+`/`-` move n_hours by half an hour, `>`/`<` move waketime by 15 minutes. n_hours stays within `[0.5, 24]`.
+pub fn handle(key: char, params: &mut Params) -> Outcome {
+	match key {
+		'+' | '=' => params.n_hours = (params.n_hours + N_HOURS_STEP).min(24.0),
+		'-' | '_' => params.n_hours = (params.n_hours - N_HOURS_STEP).max(N_HOURS_STEP),
+		'>' | '.' => params.waketime = params.waketime.shifted(WAKETIME_STEP_MINUTES),
+		'<' | ',' => params.waketime = params.waketime.shifted(-WAKETIME_STEP_MINUTES),
+		'a' => return Outcome::Apply,
+		'q' => return Outcome::Quit,
+		_ => return Outcome::Unknown,
+	}
+	Outcome::Changed
+}
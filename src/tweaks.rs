@@ -0,0 +1,81 @@
+use std::{
+	fs::File,
+	io::{BufRead, BufReader},
+	os::unix::fs::FileTypeExt,
+	path::{Path, PathBuf},
+	process::Command,
+	sync::mpsc,
+	thread,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+
+use crate::schedule::Waketime;
+
+/// Schedule parameter changed at runtime, for trying out curves without restarting
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tweak {
+	NHours(f32),
+	Waketime(Waketime),
+}
+
+/// Takes `key = value`, or just `key value`, with `key` being `n_hours` or `waketime`
+pub fn parse(line: &str) -> Result<Tweak> {
+	let (key, value) = line
+		.split_once('=')
+		.or_else(|| line.trim().split_once(char::is_whitespace))
+		.ok_or_else(|| anyhow!("expected `n_hours = <hours>` or `waketime = <HH:MM>`"))?;
+	let value = value.trim();
+	match key.trim() {
+		"n_hours" => {
+			let n_hours: f32 = value.parse().with_context(|| format!("bad n_hours {value:?}"))?;
+			ensure!(n_hours > 0.0 && n_hours <= 24.0, "n_hours has to be in (0, 24], got {n_hours}");
+			Ok(Tweak::NHours(n_hours))
+		}
+		"waketime" => Ok(Tweak::Waketime(value.parse()?)),
+		other => bail!("unknown parameter `{other}`, only n_hours and waketime can be tweaked"),
+	}
+}
+
+/// Sends every well-formed line of `lines` as a tweak, warning about the rest. Returns `false` once nobody is listening.
+pub fn forward(lines: impl BufRead, tx: &mpsc::Sender<Tweak>) -> bool {
+	for line in lines.lines().map_while(Result::ok).filter(|l| !l.trim().is_empty()) {
+		match parse(&line) {
+			Ok(tweak) => {
+				if tx.send(tweak).is_err() {
+					return false;
+				}
+			}
+			Err(e) => tracing::warn!("ignoring {line:?} from the tweak fifo: {e:#}"),
+		}
+	}
+	true
+}
+
+/// Creates the fifo at `path` if there is none, and sends on the returned channel whatever is written into it
+pub fn listen(path: &Path) -> Result<mpsc::Receiver<Tweak>> {
+	match path.metadata() {
+		Ok(m) if m.file_type().is_fifo() => {}
+		Ok(_) => bail!("{} exists and isn't a fifo", path.display()),
+		Err(_) => {
+			let status = Command::new("mkfifo").arg(path).status().context("failed to run mkfifo")?;
+			ensure!(status.success(), "failed to create a fifo at {}", path.display());
+		}
+	}
+	let path: PathBuf = path.to_owned();
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || loop {
+		// blocks until someone opens it for writing, and hits EOF once they're done, so it's reopened for the next one
+		let file = match File::open(&path) {
+			Ok(file) => file,
+			Err(e) => {
+				tracing::warn!("failed to open {}, no longer taking tweaks: {e}", path.display());
+				break;
+			}
+		};
+		if !forward(BufReader::new(file), &tx) {
+			break;
+		}
+	});
+	Ok(rx)
+}
@@ -0,0 +1,54 @@
+use std::{
+	hash::{DefaultHasher, Hash, Hasher},
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::{ensure, Context, Result};
+
+/// Hash of the full paths and modification times of `sources`, for files made of them to be told apart by,
+/// as two wallpapers can share a name across directories, and an edited one is to be made over
+pub fn cache_key(sources: &[&Path]) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	for source in sources {
+		source.hash(&mut hasher);
+		std::fs::metadata(source).and_then(|m| m.modified()).ok().hash(&mut hasher);
+	}
+	hasher.finish()
+}
+
+/// Fraction of its brightness a wallpaper is shown at for `redshift`, going linearly from 1 at 0 down to `darkest` at 20.
+/// Snapped to 5% steps, so that an evening only ever makes a handful of copies.
+pub fn dim_factor(redshift: f32, darkest: f32) -> f32 {
+	let factor = 1.0 - (1.0 - darkest.clamp(0.0, 1.0)) * (redshift / 20.0).clamp(0.0, 1.0);
+	(factor * 20.0).round() / 20.0
+}
+
+/// Copy of `path` darkened to `factor` of its brightness through imagemagick, cached under the temp dir. `path` itself at a factor of 1
+pub fn dimmed(path: &Path, factor: f32) -> Result<PathBuf> {
+	let percent = (factor * 100.0).round() as u32;
+	if percent >= 100 {
+		return Ok(path.to_owned());
+	}
+	let dir = std::env::temp_dir().join("auto_redshift").join("wallpapers");
+	let stem = path.file_stem().context("wallpaper path has no file name")?.to_string_lossy();
+	let extension = path.extension().map_or("png".into(), |e| e.to_string_lossy());
+	let out = dir.join(format!("{stem}-{:016x}-{percent}.{extension}", cache_key(&[path])));
+	if out.exists() {
+		return Ok(out);
+	}
+	std::fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+	let output = Command::new("magick")
+		.arg(path)
+		.args(["-modulate", &percent.to_string()])
+		.arg(&out)
+		.output()
+		.context("failed to run `magick`")?;
+	ensure!(
+		output.status.success(),
+		"failed to darken {}: {}",
+		path.display(),
+		String::from_utf8_lossy(&output.stderr).trim()
+	);
+	Ok(out)
+}
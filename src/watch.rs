@@ -0,0 +1,82 @@
+use std::{
+	path::{Path, PathBuf},
+	sync::mpsc,
+	time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use notify::{EventKind, RecursiveMode, Watcher};
+
+/// Collapses a burst of events, like an editor writing a file twice on save, into one, once `quiet` has passed without any more
+#[derive(Clone, Debug)]
+pub struct Debounce {
+	quiet: Duration,
+	last: Option<Instant>,
+}
+impl Debounce {
+	pub fn new(quiet: Duration) -> Self {
+		Self { quiet, last: None }
+	}
+
+	pub fn event(&mut self, at: Instant) {
+		self.last = Some(self.last.map_or(at, |last| last.max(at)));
+	}
+
+	/// Whether a burst is over by `now`. Comes out true once per burst
+	pub fn settled(&mut self, now: Instant) -> bool {
+		match self.last {
+			Some(last) if now.saturating_duration_since(last) >= self.quiet => {
+				self.last = None;
+				true
+			}
+			_ => false,
+		}
+	}
+}
+
+/// Changes to one file, watched through its directory, so that editors replacing the file on save are caught too
+pub struct ConfigWatcher {
+	_watcher: notify::RecommendedWatcher,
+	events: mpsc::Receiver<Instant>,
+	debounce: Debounce,
+}
+impl ConfigWatcher {
+	pub fn new(path: &Path) -> Result<Self> {
+		let file: PathBuf = path.file_name().context("config path has no file name")?.into();
+		let dir = match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+			Some(dir) => dir.to_owned(),
+			None => PathBuf::from("."),
+		};
+		let (tx, events) = mpsc::channel();
+		let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+			let Ok(event) = event else {
+				return;
+			};
+			if !matches!(event.kind, EventKind::Access(_)) && event.paths.iter().any(|p| p.file_name() == Some(file.as_os_str())) {
+				let _ = tx.send(Instant::now());
+			}
+		})
+		.context("failed to set up a file watcher")?;
+		watcher
+			.watch(&dir, RecursiveMode::NonRecursive)
+			.with_context(|| format!("failed to watch {}", dir.display()))?;
+		Ok(Self {
+			_watcher: watcher,
+			events,
+			debounce: Debounce::new(Duration::from_millis(500)),
+		})
+	}
+
+	/// Whether the file changed, and was then left alone for a moment
+	pub fn changed(&mut self) -> bool {
+		for at in self.events.try_iter() {
+			self.debounce.event(at);
+		}
+		self.debounce.settled(Instant::now())
+	}
+}
+impl std::fmt::Debug for ConfigWatcher {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ConfigWatcher").field("debounce", &self.debounce).finish_non_exhaustive()
+	}
+}
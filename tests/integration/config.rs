@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use auto_redshift::{
+	config::{AppConfig, ColorProfile, PhaseHooks, PhaseOverride, SeasonalHours, Wallpapers},
+	display::DisplaySettings,
+	schedule::TimeWindow,
+};
+
+fn non_default() -> AppConfig {
+	let mut config = AppConfig {
+		brightness_range: (0.25, 0.75),
+		temperature_range: (3000, 6000),
+		color_profile: Some(ColorProfile::Halogen),
+		wallpapers: Wallpapers::new("/wallpapers".into(), "a.jpg".into(), "b.jpg".into(), "c.jpg".into(), "/elsewhere/d.jpg".into()),
+		n_hours: Some(SeasonalHours::Seasons { summer: 3.0, winter: 5.0 }),
+		grace_minutes: 15.0,
+		morning_n_hours: 2.0,
+		evening_end_redshift: 16.0,
+		night_redshift: 18.0,
+		invert_brightness: true,
+		dither: 50.0,
+		soft_start_s: 2.0,
+		poll_interval_s: 7,
+		blackout: vec!["22:00-06:00".parse::<TimeWindow>().unwrap()],
+		presets: HashMap::from([("reading".to_owned(), DisplaySettings::new(4000.0, 0.8))]),
+		..Default::default()
+	};
+	config.gdbus.timeout_s = 9;
+	config.ambient.enabled = true;
+	config.ambient.weight = 0.25;
+	config.fullscreen.enabled = true;
+	config.hooks.evening = PhaseHooks {
+		on_enter: Some("notify-send evening".to_owned()),
+		on_exit: None,
+	};
+	config.overrides.night = PhaseOverride {
+		temperature: Some(2700.0),
+		brightness: None,
+	};
+	config
+}
+
+#[test]
+fn round_trips_through_toml() {
+	let config = non_default();
+	let path = std::env::temp_dir().join(format!("auto_redshift_round_trip_{}.toml", std::process::id()));
+	std::fs::write(&path, toml::to_string(&config).unwrap()).unwrap();
+	let read = AppConfig::read(&path, None, None);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(read.unwrap(), config);
+}
+
+#[test]
+fn ranges_serialize_as_arrays() {
+	let serialized = toml::to_string(&non_default()).unwrap();
+	assert!(serialized.contains("brightness_range = [0.25, 0.75]"), "{serialized}");
+	assert!(serialized.contains("temperature_range = [3000, 6000]"), "{serialized}");
+}
@@ -1 +1,2 @@
-//TODO!!!: This. Should be the entry point to all integration tests of rust projects, following https://matklad.github.io/2021/02/27/delete-cargo-integration-tests.html
+//! Entry point to all integration tests, following https://matklad.github.io/2021/02/27/delete-cargo-integration-tests.html
+mod config;